@@ -1,2 +1,2 @@
-pub mod client;
 pub mod connection;
+pub mod decoder;
@@ -1,112 +1,147 @@
-use bytes::BytesMut;
+use crate::protocol::{Codec, Frame, Join, StreamParameters};
+use bytes::{Bytes, BytesMut};
 use futures::channel::mpsc;
 use futures::sink::SinkExt;
-use futures::stream::Stream;
-use iced::futures;
-use iced::stream;
-use tokio::io;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
 const HOST_PORT: u16 = 9475;
 
-pub fn connect(addr: String, username: String) -> impl Stream<Item = Event> {
-    stream::channel(100, |mut output| async move {
-        let mut state = State::Disconnected;
-        println!("Attempt connecting to multiplayer server: {}", addr);
-        loop {
-            match &mut state {
-                State::Disconnected => {
-                    println!("Connecting to multiplayer server: {}", addr);
-                    
-                    match TcpStream::connect(format!("{addr}:{HOST_PORT}")).await {
-                        Ok(stream) => {
-                            let (sender, receiver) = mpsc::channel(100);
+/// Connects to the host, joins `channel` asking for `codec` and forwards everything the host sends to `output`.
+/// Reconnects after unexpected disconnects, and parks once the host ends the session.
+pub async fn run(addr: String, username: String, channel: String, codec: Codec, mut output: mpsc::Sender<Event>) {
+    let mut state = State::Disconnected;
+    println!("Attempt connecting to multiplayer server: {}", addr);
+    loop {
+        match &mut state {
+            State::Disconnected => {
+                println!("Connecting to multiplayer server: {}", addr);
+                
+                match TcpStream::connect(format!("{addr}:{HOST_PORT}")).await {
+                    Ok(stream) => {
+                        let (sender, receiver) = mpsc::channel(100);
 
-                            let _ = output
-                                .send(Event::Connected(Connection(sender)))
-                                .await;
+                        let _ = output
+                            .send(Event::Connected(Connection(sender)))
+                            .await;
 
-                            state = State::Connected(MultiplayerConnection::new(stream));
-                        }
-                        Err(_) => {
-                            println!("Failed to connect to multiplayer server");
-                            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                            let _ = output.send(Event::Disconnected).await;
-                        }
+                        state = State::Connected(MultiplayerConnection::new(stream));
+                    }
+                    Err(_) => {
+                        println!("Failed to connect to multiplayer server");
+                        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                        let _ = output.send(Event::Disconnected).await;
                     }
                 }
-                State::Connected(multiplayer_connection) => {
-                    // First, send the username
-                    // Wait for the socket to be writable
-                    multiplayer_connection.stream.writable().await.unwrap();
-
-                    // Try to write data, this may still fail with `WouldBlock`
-                    // if the readiness event is a false positive.
-                    match multiplayer_connection.stream.try_write(username.as_bytes()) {
-                        Ok(n) => {
-                            println!("wrote username {} bytes", n);
-                        }
-                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                            continue;
-                        }
-                        Err(e) => {
-                           println!("error: {}", e);
-                        }
+            }
+            State::Connected(multiplayer_connection) => {
+                // First, join the channel under our username
+                let join = Frame::Join(Join {
+                    username: username.clone(),
+                    channel: channel.clone(),
+                    codec,
+                });
+                match multiplayer_connection.stream.write_all(&join.encode()).await {
+                    Ok(()) => {
+                        println!("joined channel \"{}\" as {}", channel, username);
+                    }
+                    Err(e) => {
+                        println!("error: {}", e);
+                        let _ = output.send(Event::Disconnected).await;
+                        state = State::Disconnected;
+                        continue;
                     }
+                }
 
-                    // Wait for the socket to be readable
-                    match multiplayer_connection.stream.readable().await {
-                        Ok(_) => {
-                            println!("socket is readable");
-                        }
+                // Wait for the socket to be readable
+                match multiplayer_connection.stream.readable().await {
+                    Ok(_) => {
+                        println!("socket is readable");
+                    }
+                    Err(e) => {
+                        println!("error: {}", e);
+                        let _ = output.send(Event::Disconnected).await;
+                        break;
+                    }
+                }
+                loop {
+                    match multiplayer_connection.stream.read_buf(&mut multiplayer_connection.buffer).await {
+                        Ok(n) => {
+                            if n == 0 {
+                                // The remote closed the connection. For this to be
+                                // a clean shutdown, there should be no data in the
+                                // read buffer. If there is, this means that the
+                                // peer closed the socket while sending a frame.
+                                if multiplayer_connection.buffer.is_empty() {
+                                    println!("connection closed with empty buffer");
+                                    let _ = output.send(Event::Disconnected).await;
+                                    state = State::Disconnected;
+                                    break;
+                                } else {
+                                    println!("connection reset by peer");
+                                    let _ = output.send(Event::Disconnected).await;
+                                    state = State::Disconnected;
+                                    break;
+                                }
+                            }
+                            match drain_frames(&mut multiplayer_connection.buffer, &mut output).await {
+                                Ok(None) => {}
+                                Ok(Some(reason)) => {
+                                    println!("host ended the session: {}", reason);
+                                    let _ = output.send(Event::SessionEnded(reason)).await;
+                                    state = State::Ended;
+                                    break;
+                                }
+                                Err(e) => {
+                                    println!("error: {}", e);
+                                    let _ = output.send(Event::Disconnected).await;
+                                    state = State::Disconnected;
+                                    break;
+                                }
+                            }
+                        },
                         Err(e) => {
                             println!("error: {}", e);
                             let _ = output.send(Event::Disconnected).await;
+                            state = State::Disconnected;
                             break;
                         }
                     }
-                    loop {
-                        match multiplayer_connection.stream.read_buf(&mut multiplayer_connection.buffer).await {
-                            Ok(n) => {
-                                if n == 0 {
-                                    // The remote closed the connection. For this to be
-                                    // a clean shutdown, there should be no data in the
-                                    // read buffer. If there is, this means that the
-                                    // peer closed the socket while sending a frame.
-                                    if multiplayer_connection.buffer.is_empty() {
-                                        println!("connection closed with empty buffer");
-                                        let _ = output.send(Event::Disconnected).await;
-                                        state = State::Disconnected;
-                                        break;
-                                    } else {
-                                        println!("connection reset by peer");
-                                        let _ = output.send(Event::Disconnected).await;
-                                        state = State::Disconnected;
-                                        break;
-                                    }
-                                }
-                                let _ = output.send(Event::DataReceived(multiplayer_connection.buffer.clone())).await;
-                                multiplayer_connection.buffer.clear();
-                            },
-                            Err(e) => {
-                                println!("error: {}", e);
-                                let _ = output.send(Event::Disconnected).await;
-                                state = State::Disconnected;
-                                break;
-                            }
-                        }
-                    }
                 }
             }
+            State::Ended => {
+                // The host said goodbye, so wait here until the caller drops us instead of reconnecting
+                futures::future::pending::<()>().await;
+            }
         }
-    })
+    }
+}
+
+/// Forwards every complete frame in `buffer` and returns the reason if the host ended the session.
+async fn drain_frames(buffer: &mut BytesMut, output: &mut mpsc::Sender<Event>) -> anyhow::Result<Option<String>> {
+    while let Some(frame) = Frame::decode(buffer)? {
+        match frame {
+            Frame::Audio(packet) => {
+                let _ = output.send(Event::DataReceived(packet)).await;
+            }
+            Frame::Keepalive => {
+                let _ = output.send(Event::HostSilent).await;
+            }
+            Frame::Stream(parameters) => {
+                let _ = output.send(Event::StreamChanged(parameters)).await;
+            }
+            Frame::EndSession(reason) => return Ok(Some(reason)),
+            Frame::Join(_) => anyhow::bail!("Unexpected join frame from host"),
+        }
+    }
+    Ok(None)
 }
 
 #[derive(Debug)]
 enum State {
     Disconnected,
     Connected(MultiplayerConnection),
+    Ended,
 }
 
 #[derive(Debug)]
@@ -128,7 +163,10 @@ impl MultiplayerConnection {
 pub enum Event {
     Connected(Connection),
     Disconnected,
-    DataReceived(BytesMut),
+    DataReceived(Bytes),
+    StreamChanged(StreamParameters),
+    HostSilent,
+    SessionEnded(String),
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,58 @@
+use crate::lossless::{self, flac};
+use crate::multistream::{MultistreamDecoder, MAX_FRAME_SIZE};
+use crate::protocol::{ChannelLayout, Codec, StreamParameters};
+
+enum CodecDecoder {
+    Opus {
+        decoder: MultistreamDecoder,
+        buffer: Vec<f32>,
+    },
+    Pcm,
+    Flac,
+}
+
+/// Decodes the packets of whichever codec the host sends, as described by its latest stream parameters.
+pub struct StreamDecoder {
+    layout: ChannelLayout,
+    codec: CodecDecoder,
+}
+
+impl StreamDecoder {
+    pub fn new(parameters: &StreamParameters) -> anyhow::Result<Self> {
+        let codec = match parameters.codec {
+            Codec::Opus => CodecDecoder::Opus {
+                decoder: MultistreamDecoder::new(parameters)?,
+                buffer: vec![0f32; MAX_FRAME_SIZE * parameters.layout.channels()],
+            },
+            Codec::Pcm => CodecDecoder::Pcm,
+            Codec::Flac => CodecDecoder::Flac,
+        };
+        Ok(Self {
+            layout: parameters.layout,
+            codec,
+        })
+    }
+
+    pub fn layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    /// Replaces the contents of `output` with the packet's interleaved samples, in the stream's layout.
+    pub fn decode(&mut self, packet: &[u8], output: &mut Vec<f32>) -> anyhow::Result<()> {
+        output.clear();
+        let channels = self.layout.channels();
+        match &mut self.codec {
+            CodecDecoder::Opus { decoder, buffer } => {
+                let samples = decoder.decode_float(packet, buffer)?;
+                output.extend_from_slice(&buffer[..samples * channels]);
+            }
+            CodecDecoder::Pcm => lossless::decode_pcm(packet, output)?,
+            CodecDecoder::Flac => flac::decode(packet, channels, output)?,
+        }
+        // A truncated frame would shift every channel after it
+        if !output.len().is_multiple_of(channels) {
+            anyhow::bail!("Packet of {} samples doesn't fill {} channels", output.len(), channels);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,11 @@
+pub mod client;
+pub mod effects;
+pub mod host;
+pub mod meter;
+pub mod mixer;
+pub mod quality;
+pub mod scenes;
+pub mod sfx;
+pub mod shortcuts;
+pub mod talk;
+pub mod track;
@@ -1,8 +1,12 @@
-use crate::client::connection;
+use super::meter::{meter_view, MeterDisplay, METER_INTERVAL};
+use multiplayer::client::connection;
+use multiplayer::client::decoder::StreamDecoder;
+use multiplayer::meter::LevelMeter;
+use multiplayer::multistream;
+use multiplayer::protocol::Codec;
 use iced::alignment::{Horizontal, Vertical};
-use iced::widget::{column, container, Button, Column, Container, Row, Text, TextInput};
+use iced::widget::{column, container, pick_list, Button, Column, Container, Row, Text, TextInput};
 use iced::{Alignment, Element, Event, Length, Subscription, Task};
-use opus::Channels::Stereo;
 use rodio::buffer::SamplesBuffer;
 use rodio::OutputStream;
 use serde::{Deserialize, Serialize};
@@ -12,22 +16,32 @@ const SERVER_PORT: u16 = 9475;
 pub struct Client {
     username: String,
     server_address: String,
+    channel: String,
+    codec: Codec,
     state: State,
-    opus_decoder: opus::Decoder,
+    decoder: Option<StreamDecoder>,
+    decode_buffer: Vec<f32>,
     output_stream: OutputStream,
     sink: rodio::Sink,
     ready: bool,
+    notice: Option<String>,
+    host_silent: bool,
+    level_meter: LevelMeter,
+    meter: MeterDisplay,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     UsernameChanged(String),
     ServerAddressChanged(String),
+    ChannelChanged(String),
+    CodecSelected(Codec),
     ClearPressed,
     ConnectPressed,
     DisconnectPressed,
     ConnectionEvent(connection::Event),
     Event(Event),
+    TickMeter,
     Send(connection::Message),
 }
 
@@ -39,8 +53,6 @@ enum State {
 
 impl Default for Client {
     fn default() -> Self {
-
-        let opus_decoder = opus::Decoder::new(48000, Stereo).unwrap();
         let stream_handle = rodio::OutputStreamBuilder::open_default_stream()
             .expect("open default audio stream");
         let sink = rodio::Sink::connect_new(&stream_handle.mixer());
@@ -48,11 +60,18 @@ impl Default for Client {
         Self {
             username: String::from("Username"),
             server_address: String::from("192.168.0.31"),
+            channel: String::new(),
+            codec: Codec::default(),
             state: State::Disconnected,
-            opus_decoder,
+            decoder: None,
+            decode_buffer: Vec::new(),
             output_stream: stream_handle,
             sink,
             ready: false,
+            notice: None,
+            host_silent: false,
+            level_meter: LevelMeter::new(),
+            meter: MeterDisplay::default(),
         }
     }
 }
@@ -66,7 +85,11 @@ impl Client {
     pub fn subscription(&self) -> Subscription<Message> {
         match self.ready {
             true => {
-                Subscription::run_with_id("main" ,connection::connect(self.server_address.clone(), self.username.clone())).map(Message::ConnectionEvent)
+                let (server_address, username, channel, codec) = (self.server_address.clone(), self.username.clone(), self.channel.clone(), self.codec);
+                Subscription::batch([
+                    Subscription::run_with_id("main", iced::stream::channel(100, move |output| connection::run(server_address, username, channel, codec, output))).map(Message::ConnectionEvent),
+                    iced::time::every(METER_INTERVAL).map(|_| Message::TickMeter),
+                ])
             }
             false => {
                 iced::event::listen().map(Message::Event)
@@ -86,6 +109,16 @@ impl Client {
 
                 Task::none()
             },
+            Message::ChannelChanged(channel) => {
+                self.channel = channel;
+
+                Task::none()
+            },
+            Message::CodecSelected(codec) => {
+                self.codec = codec;
+
+                Task::none()
+            },
             Message::ClearPressed => {
                 
                 Task::none()
@@ -93,6 +126,7 @@ impl Client {
             Message::ConnectPressed => {
                 self.state = State::Connecting;
                 self.ready = true;
+                self.notice = None;
 
                 Task::none()
 
@@ -119,6 +153,8 @@ impl Client {
                 connection::Event::Connected(connection) => {
                     println!("Received Connected Event");
                     self.state = State::Connected(connection);
+                    self.host_silent = false;
+                    self.meter.reset();
 
                     Task::none()
                 }
@@ -130,19 +166,62 @@ impl Client {
 
                     Task::none()
                 }
+                connection::Event::SessionEnded(reason) => {
+                    println!("Received SessionEnded Event");
+                    self.state = State::Disconnected;
+                    self.ready = false;
+                    self.sink.stop();
+                    self.notice = Some(format!("Host ended the session: {}", reason));
+
+                    Task::none()
+                }
+                connection::Event::StreamChanged(parameters) => {
+                    println!("Received StreamChanged Event: {:?}", parameters);
+                    match StreamDecoder::new(&parameters) {
+                        Ok(decoder) => {
+                            self.decoder = Some(decoder);
+                        }
+                        Err(e) => {
+                            println!("error: {}", e);
+                            self.decoder = None;
+                        }
+                    }
+
+                    Task::none()
+                }
+                connection::Event::HostSilent => {
+                    self.host_silent = true;
+
+                    Task::none()
+                }
                 connection::Event::DataReceived(data) => {
-                    let mut opus_decoder_buffer = [0f32; 960];
-                    match self.opus_decoder.decode_float(&data, opus_decoder_buffer.as_mut_slice(), false) {
-                        Ok(_result) => {
-                            let samples_buffer = SamplesBuffer::new(2, 48000, opus_decoder_buffer);
-                            self.sink.append(samples_buffer); 
+                    self.host_silent = false;
+                    // Packets before the stream parameters can't be decoded
+                    let Some(decoder) = self.decoder.as_mut() else {
+                        return Task::none();
+                    };
+                    match decoder.decode(&data, &mut self.decode_buffer) {
+                        Ok(()) => {
+                            let layout = decoder.layout();
+                            let output_channels = self.output_stream.config().channel_count();
+                            let decoded = self.decode_buffer.as_slice();
+                            self.level_meter.process(decoded, layout.channels());
+                            let mixed = multistream::downmix(layout, decoded, output_channels as usize);
+                            let channels = (output_channels as usize).min(layout.channels()) as u16;
+                            let samples_buffer = SamplesBuffer::new(channels, 48000, mixed);
+                            self.sink.append(samples_buffer);
                         }
                         Err(e) => println!("error: {}", e)
-                    } 
+                    }
 
                     Task::none()
                 }
             },
+            Message::TickMeter => {
+                self.meter.update(self.level_meter.take());
+
+                Task::none()
+            },
             Message::Event(event) => match event {
                 Event::Keyboard(iced::keyboard::Event::KeyPressed {
                                     key: iced::keyboard::Key::Named(iced::keyboard::key::Named::Tab),
@@ -166,9 +245,15 @@ impl Client {
                 container(
                     column![
                         Container::new(Text::new("Connected").center().align_x(Horizontal::Center)),
-                        Button::new(Text::new("Disconnect").center().align_x(Horizontal::Center))
-                            .on_press(Message::DisconnectPressed),
                     ]
+                        .push_maybe(
+                            self.host_silent.then(|| Text::new("Host is silent").center().align_x(Horizontal::Center))
+                        )
+                        .push(meter_view(&self.meter))
+                        .push(
+                            Button::new(Text::new("Disconnect").center().align_x(Horizontal::Center))
+                                .on_press(Message::DisconnectPressed),
+                        )
                 )
                     .width(Length::Fill)
                     .height(Length::Fill)
@@ -183,6 +268,9 @@ impl Client {
                         .max_width(600)
                         .padding(20)
                         .spacing(16)
+                        .push_maybe(
+                            self.notice.as_ref().map(|notice| Text::new(notice).size(20))
+                        )
                         .push(
                             TextInput::new("Username", &self.username)
                                 .on_input(Message::UsernameChanged)
@@ -195,6 +283,24 @@ impl Client {
                                 .padding(10)
                                 .size(32)
                         )
+                        .push(
+                            TextInput::new("Channel (empty for the host's first channel)", &self.channel)
+                                .on_input(Message::ChannelChanged)
+                                .padding(10)
+                                .size(32)
+                        )
+                        .push(
+                            Row::new()
+                                .spacing(10)
+                                .align_y(Alignment::Center)
+                                .push(Text::new("Stream as").size(20))
+                                .push(
+                                    pick_list(Codec::ALL, Some(self.codec), Message::CodecSelected)
+                                        .padding(10)
+                                        .text_size(20)
+                                        .width(Length::Fill)
+                                )
+                        )
                         .push(
                             Row::new()
                                 .spacing(10)
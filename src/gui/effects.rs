@@ -0,0 +1,113 @@
+use iced::widget::{column, container, pick_list, row, slider, text, toggler};
+use iced::{Element, Fill, FillPortion};
+use multiplayer::host::effects::{Effects, Equalizer, Filter, FilterKind, Reverb};
+
+// The cutoff slider is logarithmic, from 20 Hz to 20 kHz
+const LOWEST_CUTOFF: f64 = 20.0;
+const CUTOFF_DECADES: f64 = 3.0;
+
+fn cutoff_to_slider(cutoff: f64) -> f64 {
+    (cutoff / LOWEST_CUTOFF).log10() / CUTOFF_DECADES
+}
+
+fn slider_to_cutoff(position: f64) -> f64 {
+    LOWEST_CUTOFF * 10f64.powf(position * CUTOFF_DECADES)
+}
+
+fn labeled<'a, M: 'a>(label: &'a str, control: impl Into<Element<'a, M>>, value: String) -> Element<'a, M> {
+    row![
+        text(label).width(FillPortion(1)),
+        container(control).width(FillPortion(3)),
+        text(value).width(FillPortion(1)),
+    ]
+        .spacing(8)
+        .into()
+}
+
+/// Every change sends the whole of `effects` with it changed through `on_change`, for the track or the room.
+pub fn effects_view<'a, M: Clone + 'a>(title: &'a str, effects: Effects, on_change: impl Fn(Effects) -> M + Copy + 'a) -> Element<'a, M> {
+    let Effects { filter, equalizer, reverb } = effects;
+    let mut content = column![text(title).size(16)];
+
+    let with_filter = move |change: &dyn Fn(&mut Filter)| {
+        let mut filter = filter;
+        change(&mut filter);
+        on_change(Effects { filter, ..effects })
+    };
+    content = content.push(toggler(filter.enabled).label("Filter").on_toggle(move |enabled| with_filter(&|filter| filter.enabled = enabled)));
+    if filter.enabled {
+        content = content
+            .push(labeled(
+                "Type",
+                pick_list(FilterKind::ALL, Some(filter.kind), move |kind| with_filter(&|filter| filter.kind = kind)),
+                String::new(),
+            ))
+            .push(labeled(
+                "Cutoff",
+                slider(0.0..=1.0, cutoff_to_slider(filter.cutoff), move |position| {
+                    with_filter(&|filter| filter.cutoff = slider_to_cutoff(position))
+                }).step(0.001),
+                format!("{:.0} Hz", filter.cutoff),
+            ))
+            .push(labeled(
+                "Resonance",
+                slider(0.0..=1.0, filter.resonance, move |resonance| with_filter(&|filter| filter.resonance = resonance)).step(0.01),
+                format!("{:.2}", filter.resonance),
+            ));
+    }
+
+    let with_equalizer = move |change: &dyn Fn(&mut Equalizer)| {
+        let mut equalizer = equalizer;
+        change(&mut equalizer);
+        on_change(Effects { equalizer, ..effects })
+    };
+    content = content.push(toggler(equalizer.enabled).label("Equalizer").on_toggle(move |enabled| with_equalizer(&|equalizer| equalizer.enabled = enabled)));
+    if equalizer.enabled {
+        content = content
+            .push(labeled(
+                "Low",
+                slider(-24.0..=12.0, equalizer.low, move |gain| with_equalizer(&|equalizer| equalizer.low = gain)).step(0.5),
+                format!("{:+.1} dB", equalizer.low),
+            ))
+            .push(labeled(
+                "Mid",
+                slider(-24.0..=12.0, equalizer.mid, move |gain| with_equalizer(&|equalizer| equalizer.mid = gain)).step(0.5),
+                format!("{:+.1} dB", equalizer.mid),
+            ))
+            .push(labeled(
+                "High",
+                slider(-24.0..=12.0, equalizer.high, move |gain| with_equalizer(&|equalizer| equalizer.high = gain)).step(0.5),
+                format!("{:+.1} dB", equalizer.high),
+            ));
+    }
+
+    let with_reverb = move |change: &dyn Fn(&mut Reverb)| {
+        let mut reverb = reverb;
+        change(&mut reverb);
+        on_change(Effects { reverb, ..effects })
+    };
+    content = content.push(toggler(reverb.enabled).label("Reverb").on_toggle(move |enabled| with_reverb(&|reverb| reverb.enabled = enabled)));
+    if reverb.enabled {
+        content = content
+            .push(labeled(
+                "Room size",
+                slider(0.0..=0.99, reverb.size, move |size| with_reverb(&|reverb| reverb.size = size)).step(0.01),
+                format!("{:.2}", reverb.size),
+            ))
+            .push(labeled(
+                "Damping",
+                slider(0.0..=1.0, reverb.damping, move |damping| with_reverb(&|reverb| reverb.damping = damping)).step(0.01),
+                format!("{:.2}", reverb.damping),
+            ))
+            .push(labeled(
+                "Mix",
+                slider(0.0..=1.0, reverb.mix, move |mix| with_reverb(&|reverb| reverb.mix = mix)).step(0.01),
+                format!("{:.0}%", reverb.mix * 100.0),
+            ));
+    }
+
+    container(content.spacing(6))
+        .width(Fill)
+        .padding([6, 40])
+        .into()
+}
@@ -0,0 +1,1200 @@
+use super::effects::effects_view;
+use super::meter::{meter_view, MeterDisplay, METER_INTERVAL};
+use super::mixer::mixer_view;
+use super::scenes::{scenes_view, SceneMessage};
+use super::sfx::sfx_view;
+use super::quality::{quality_view, update_quality, StreamQualityMessage};
+use super::shortcuts::{key_binding, shortcuts_view, track_number, ShortcutsMessage};
+use super::talk::{push_to_talk_button, talk_over_view, update_talk_over, TalkOverMessage};
+use super::track::playlist_view;
+
+use multiplayer::host::backend::OutputBackend;
+use multiplayer::host::encoder::EncoderStatus;
+use multiplayer::host::effects::Effects;
+use multiplayer::host::loudness::{self, Measurement};
+use multiplayer::host::playlist::Playlist;
+use multiplayer::host::room::Room;
+use multiplayer::host::server::{self, Channel, ConnectedClient};
+use multiplayer::host::sound::SoundData;
+use multiplayer::host::source::microphone::Microphone;
+use multiplayer::host::track::{Error, MultiplayerPlaylist, MultiplayerPlaylistMessage, MultiplayerTrack, MultiplayerTrackMessage};
+use multiplayer::protocol::ChannelLayout;
+use multiplayer::settings;
+use iced::alignment::Horizontal;
+use iced::widget::{button, center, column, container, pick_list, row, slider, text, text_input, toggler, tooltip, vertical_space, Column, Container, Row, Scrollable, Space, Text};
+use iced::keyboard::{self, Key, Modifiers};
+use iced::{event, Alignment, Color, Element, Event, Fill, FillPortion, Font, Subscription, Task};
+use kira::sound::PlaybackState;
+use kira::Frame;
+use kira::{AudioManager, AudioManagerSettings};
+use rfd::FileHandle;
+use rodio::Source;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::io;
+
+// Has to be shorter than the room's schedule-ahead time, or crossfades start late
+const PLAYLIST_INTERVAL: Duration = Duration::from_millis(100);
+// How much the volume shortcuts change the current track's volume
+const VOLUME_STEP: f64 = 0.05;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    OpenFiles,
+    FilesOpened(Result<Vec<MultiplayerTrack>, Error>),
+    ImportPlaylist,
+    PlaylistImported(Result<MultiplayerPlaylist, Error>),
+    ExportPlaylist,
+    PlaylistExported(Result<FileHandle, Error>),
+    PlaylistSavedToFile(Result<(), Error>),
+    MultiplayerPlaylist(MultiplayerPlaylistMessage),
+    UpdatePlaybackPositionSlider(f64),
+    SeekToPlaybackPosition,
+    TickPlaybackPosition,
+    TickMeters,
+    TickPlaylist,
+    UpdateFadeInDurationSlider(f64),
+    UpdateFadeOutDurationSlider(f64),
+    /// Sent when a slider is let go. Sliders save the settings then, not on every step they're dragged through.
+    SaveSettings,
+    Pause,
+    Resume,
+    Stop,
+    StopAll,
+    SelectRoom(usize),
+    NewRoomNameChanged(String),
+    AddRoom,
+    RemoveRoom,
+    BroadcastOnlyToggled(bool),
+    PlaybackModeSelected(settings::PlaybackMode),
+    UpdateCrossfadeOverlapSlider(f64),
+    UpdateSfxVolumeSlider(f64),
+    UpdateSfxPolyphonySlider(f64),
+    NormalizeLoudnessToggled(bool),
+    UpdateTargetLoudnessSlider(f64),
+    LoudnessAnalyzed(String, Option<Measurement>),
+    ToggleStreamQuality,
+    StreamQuality(StreamQualityMessage),
+    ToggleTalkOver,
+    TalkOver(TalkOverMessage),
+    RestartEncoder,
+    ToggleMixer,
+    ToggleEffects,
+    MasterEffectsChanged(Effects),
+    ToggleScenes,
+    Scenes(SceneMessage),
+    ToggleShortcuts,
+    Shortcuts(ShortcutsMessage),
+    KeyPressed(Key, Modifiers),
+    Server,
+}
+
+pub struct Host {
+    is_loading: bool,
+    audio_manager: AudioManager<OutputBackend>,
+    audio_output: settings::AudioOutput,
+    local_playback: bool,
+    broadcast_only: bool,
+    playback_mode: settings::PlaybackMode,
+    crossfade_overlap: u64,
+    sfx_volume: f64,
+    sfx_polyphony: usize,
+    normalize_loudness: bool,
+    target_loudness: f64,
+    channel_layout: ChannelLayout,
+    stream_quality: settings::StreamQuality,
+    show_stream_quality: bool,
+    talk_over: settings::TalkOver,
+    show_talk_over: bool,
+    show_mixer: bool,
+    show_effects: bool,
+    show_scenes: bool,
+    new_scene_name: String,
+    scene_transition: u64,
+    microphone: Option<Microphone>,
+    microphone_error: Option<String>,
+    talking: bool,
+    ducked: bool,
+    shortcuts: settings::Shortcuts,
+    show_shortcuts: bool,
+    recording_shortcut: Option<settings::Shortcut>,
+    meter: MeterDisplay,
+    rooms: Vec<Room>,
+    selected_room: usize,
+    new_room_name: String,
+    pub fade_in_duration: u64,
+    pub fade_out_duration: u64,
+    audio_seek_dragged: bool,
+    pub connected_clients: Arc<Mutex<HashMap<SocketAddr, ConnectedClient>>>,
+    channels: Arc<Mutex<Vec<Channel>>>,
+    pub server_running: bool,
+    tx_shutdown: Option<tokio::sync::watch::Sender<Option<String>>>,
+}
+
+
+
+impl Host {
+    
+    pub fn new(settings: settings::Settings) -> (Self, Task<Message>) {
+        let (tx_shutdown, rx_shutdown) = tokio::sync::watch::channel(None);
+
+        let mut audio_manager = AudioManager::<OutputBackend>::new(AudioManagerSettings {
+            backend_settings: settings.audio_output,
+            ..Default::default()
+        }).unwrap();
+        let local_playback = !audio_manager.backend_mut().is_null();
+        audio_manager.backend_mut().set_local_playback(!settings.broadcast_only);
+        let mut room_names = settings.rooms;
+        if room_names.is_empty() {
+            room_names.push(String::from(settings::DEFAULT_ROOM_NAME));
+        }
+        let rooms = room_names.into_iter()
+            .map(|name| {
+                let mut room = Room::new(name, settings.channel_layout, settings.stream_quality, &mut audio_manager);
+                room.set_playback_mode(settings.playback_mode);
+                room.set_sfx_volume(settings.sfx_volume);
+                room.set_sfx_polyphony(settings.sfx_polyphony);
+                room.set_normalization(settings.normalize_loudness.then_some(settings.target_loudness));
+                room
+            })
+            .collect::<Vec<Room>>();
+        let channels = Arc::new(Mutex::new(rooms.iter().map(Room::channel).collect::<Vec<Channel>>()));
+
+        let connected_clients = Arc::new(Mutex::new(HashMap::new()));
+
+        let task = Task::perform(server::run(connected_clients.clone(), channels.clone(), rx_shutdown), |result| {
+            if let Err(e) = result {
+                println!("Server exited with error: {}", e);
+            }
+            Message::Server
+        });
+        
+        let host = Self {
+            is_loading: false,
+            audio_manager,
+            audio_output: settings.audio_output,
+            local_playback,
+            broadcast_only: settings.broadcast_only,
+            playback_mode: settings.playback_mode,
+            crossfade_overlap: settings.crossfade_overlap,
+            sfx_volume: settings.sfx_volume,
+            sfx_polyphony: settings.sfx_polyphony,
+            normalize_loudness: settings.normalize_loudness,
+            target_loudness: settings.target_loudness,
+            channel_layout: settings.channel_layout,
+            stream_quality: settings.stream_quality,
+            show_stream_quality: false,
+            talk_over: settings.talk_over,
+            show_talk_over: false,
+            show_mixer: false,
+            show_effects: false,
+            show_scenes: false,
+            new_scene_name: String::new(),
+            scene_transition: settings.scene_transition,
+            microphone: None,
+            microphone_error: None,
+            talking: false,
+            ducked: false,
+            shortcuts: settings.shortcuts,
+            show_shortcuts: false,
+            recording_shortcut: None,
+            meter: MeterDisplay::default(),
+            rooms,
+            selected_room: 0,
+            new_room_name: String::new(),
+            fade_in_duration: settings.fade_in_duration,
+            fade_out_duration: settings.fade_out_duration,
+            audio_seek_dragged: false,
+            connected_clients: connected_clients.clone(),
+            channels,
+            server_running: true,
+            tx_shutdown: Some(tx_shutdown),
+        };
+
+        (host, task)
+    }
+
+    pub fn settings(&self) -> settings::Settings {
+        settings::Settings {
+            fade_in_duration: self.fade_in_duration,
+            fade_out_duration: self.fade_out_duration,
+            mode: settings::Mode::Host,
+            rooms: self.rooms.iter().map(|room| room.name.clone()).collect(),
+            audio_output: self.audio_output,
+            broadcast_only: self.broadcast_only,
+            channel_layout: self.channel_layout,
+            stream_quality: self.stream_quality,
+            talk_over: self.talk_over,
+            playback_mode: self.playback_mode,
+            crossfade_overlap: self.crossfade_overlap,
+            shortcuts: self.shortcuts.clone(),
+            sfx_volume: self.sfx_volume,
+            sfx_polyphony: self.sfx_polyphony,
+            scene_transition: self.scene_transition,
+            normalize_loudness: self.normalize_loudness,
+            target_loudness: self.target_loudness,
+        }
+    }
+
+    /// Saving can fail, say on a read-only config directory, which shouldn't take the host down with it.
+    pub fn save_settings(&self) {
+        if let Err(e) = settings::save(&self.settings()) {
+            println!("Error saving settings: {}", e);
+        }
+    }
+
+    fn normalize_to(&self) -> Option<f64> {
+        self.normalize_loudness.then_some(self.target_loudness)
+    }
+
+    /// Measures the loudness of the selected room's tracks that haven't been yet, each in the background.
+    /// Tracks from playlists saved before peaks were measured are measured again for their peak.
+    fn analyze_loudness(&mut self) -> Task<Message> {
+        let mut tasks = Vec::new();
+        for track in self.room_mut().playlist.tracks.iter_mut().filter(|track| track.peak.is_none() && !track.analyzing) {
+            track.analyzing = true;
+            let (path, data) = (track.path.clone(), track.data.clone());
+            tasks.push(Task::perform(measure_loudness(data), move |loudness| Message::LoudnessAnalyzed(path.clone(), loudness)));
+        }
+        Task::batch(tasks)
+    }
+
+    fn room(&self) -> &Room {
+        &self.rooms[self.selected_room]
+    }
+
+    fn room_mut(&mut self) -> &mut Room {
+        &mut self.rooms[self.selected_room]
+    }
+
+    /// Stops every room's encoder and tells every connected client why the session is ending.
+    /// The server task finishes with `Message::Server` once all clients have been closed.
+    pub fn end_session(&mut self, reason: &str) {
+        for room in self.rooms.iter_mut() {
+            room.close(None);
+        }
+        if let Some(tx_shutdown) = self.tx_shutdown.take() {
+            tx_shutdown.send_replace(Some(reason.to_string()));
+        }
+    }
+
+    pub fn subscription(&self) -> Subscription<Message> {
+        let meters = iced::time::every(METER_INTERVAL).map(|_| Message::TickMeters);
+        // Keys typed into a text input are captured by it and never become shortcuts
+        let keys = iced::event::listen_with(|event, status, _window| match (event, status) {
+            (Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }), event::Status::Ignored) => {
+                Some(Message::KeyPressed(key, modifiers))
+            },
+            _ => None,
+        });
+        if self.audio_seek_dragged {
+            return Subscription::batch([meters, keys])
+        }
+        
+        Subscription::batch([
+            iced::time::every(Duration::from_secs_f64(1.0)).map(|_| Message::TickPlaybackPosition),
+            iced::time::every(PLAYLIST_INTERVAL).map(|_| Message::TickPlaylist),
+            meters,
+            keys,
+        ])
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        match message {
+            Message::OpenFiles => {
+                if self.is_loading {
+                    Task::none()
+                } else {
+                    self.is_loading = true;
+
+                    Task::perform(open_files(), Message::FilesOpened)
+                }
+            }
+            Message::FilesOpened(result) => {
+                self.is_loading = false;
+
+                if let Ok(tracks) = result {
+                    for track in tracks {
+                        self.room_mut().playlist.add_track(track)
+                    }
+                }
+
+                self.analyze_loudness()
+            }
+
+            Message::ImportPlaylist => {
+                if self.is_loading {
+                    Task::none()
+                } else {
+                    self.is_loading = true;
+
+                    Task::perform(open_playlist(), Message::PlaylistImported)
+                }
+            },
+
+            Message::PlaylistImported(result) => {
+                if let Ok(playlist) = result {
+                    self.room_mut().replace_playlist(playlist);
+                }
+                self.is_loading = false;
+
+                self.analyze_loudness()
+            }
+
+            Message::ExportPlaylist => {
+                if self.is_loading {
+                    Task::none()
+                } else {
+                    self.is_loading = true;
+
+                    Task::perform(save_playlist(), Message::PlaylistExported)
+                }
+            },
+
+            Message::PlaylistExported(result) => {
+                match result {
+                    Ok(path) => {
+                        let playlist = self.room().playlist.to_playlist();
+                        let playlist_json = serde_json::to_string(&playlist).unwrap();
+
+                        Task::perform(save_playlist_to_file(path, playlist_json), Message::PlaylistSavedToFile)
+                    }
+                    Err(_) => {
+                        self.is_loading = false;
+                        Task::none()
+                    }
+                }
+            },
+
+            Message::PlaylistSavedToFile(result) => {
+                if let Err(e) = result {
+                    println!("Error saving playlist to file: {:#?}", e);
+                }
+                self.is_loading = false;
+                Task::none()
+            }
+
+            Message::MultiplayerPlaylist(message) => {
+                let (fade_in_duration, fade_out_duration) = (self.fade_in_duration, self.fade_out_duration);
+                self.room_mut().update(message, fade_in_duration, fade_out_duration);
+
+                Task::none()
+            },
+            Message::UpdatePlaybackPositionSlider(slider_position) => {
+                self.audio_seek_dragged = true;
+                self.room_mut().playback_position = slider_position;
+
+                Task::none()
+            },
+            Message::SeekToPlaybackPosition => {
+                self.room_mut().seek();
+                self.audio_seek_dragged = false;
+
+                Task::none()
+            },
+            Message::TickPlaybackPosition => {
+                for room in self.rooms.iter_mut() {
+                    room.tick();
+                }
+
+                Task::none()
+            },
+            Message::TickMeters => {
+                // Every room's meter is taken so a room shows fresh levels when it gets selected
+                for (index, room) in self.rooms.iter().enumerate() {
+                    let levels = room.take_levels();
+                    if index == self.selected_room {
+                        self.meter.update(levels);
+                    }
+                }
+                // The music ducks while the microphone's gate is open and comes back once it closes
+                let open = self.microphone.as_ref().is_some_and(Microphone::is_open);
+                if open != self.ducked {
+                    self.ducked = open;
+                    for room in self.rooms.iter_mut() {
+                        room.set_ducked(open, self.talk_over.duck_depth, self.talk_over.release);
+                    }
+                }
+
+                Task::none()
+            },
+            Message::TickPlaylist => {
+                for room in self.rooms.iter_mut() {
+                    room.advance(self.crossfade_overlap, self.fade_out_duration);
+                }
+
+                Task::none()
+            },
+            Message::UpdateFadeInDurationSlider(fade_in) => {
+                self.fade_in_duration = fade_in as u64;
+
+                Task::none()
+            },
+            Message::UpdateFadeOutDurationSlider(fade_out) => {
+                self.fade_out_duration = fade_out as u64;
+
+                Task::none()
+            },
+            Message::SaveSettings => {
+                self.save_settings();
+
+                Task::none()
+            },
+
+            Message::Pause => {
+                let fade_out_duration = self.fade_out_duration;
+                self.room_mut().pause(fade_out_duration);
+
+                Task::none()
+            },
+
+            Message::Resume => {
+                let fade_in_duration = self.fade_in_duration;
+                self.room_mut().resume(fade_in_duration);
+
+                Task::none()
+            }
+
+            Message::Stop => {
+                let fade_out_duration = self.fade_out_duration;
+                self.room_mut().stop(fade_out_duration);
+
+                Task::none()
+            }
+            Message::StopAll => {
+                let fade_out_duration = self.fade_out_duration;
+                self.room_mut().stop_all(fade_out_duration);
+
+                Task::none()
+            }
+            Message::SelectRoom(index) => {
+                if index < self.rooms.len() {
+                    self.selected_room = index;
+                    self.meter.reset();
+                }
+
+                Task::none()
+            }
+            Message::NewRoomNameChanged(name) => {
+                self.new_room_name = name;
+
+                Task::none()
+            }
+            Message::AddRoom => {
+                let name = self.new_room_name.trim().to_string();
+                if name.is_empty() || self.rooms.iter().any(|room| room.name.eq_ignore_ascii_case(&name)) {
+                    return Task::none();
+                }
+                let mut room = Room::new(name, self.channel_layout, self.stream_quality, &mut self.audio_manager);
+                room.set_playback_mode(self.playback_mode);
+                room.set_sfx_volume(self.sfx_volume);
+                room.set_sfx_polyphony(self.sfx_polyphony);
+                room.set_normalization(self.normalize_to());
+                if let Some(microphone) = &self.microphone {
+                    room.set_overlay(Some(Box::new(microphone.receiver())));
+                }
+                if self.ducked {
+                    room.set_ducked(true, self.talk_over.duck_depth, 0);
+                }
+                self.channels.lock().unwrap().push(room.channel());
+                self.rooms.push(room);
+                self.selected_room = self.rooms.len() - 1;
+                self.meter.reset();
+                self.new_room_name.clear();
+                self.save_settings();
+
+                Task::none()
+            }
+            Message::RemoveRoom => {
+                if self.rooms.len() <= 1 {
+                    return Task::none();
+                }
+                let mut room = self.rooms.remove(self.selected_room);
+                self.channels.lock().unwrap().retain(|channel| channel.name != room.name);
+                room.close(Some(&format!("Channel {} was closed by the host", room.name)));
+                self.selected_room = self.selected_room.min(self.rooms.len() - 1);
+                self.meter.reset();
+                self.save_settings();
+
+                Task::none()
+            }
+            Message::BroadcastOnlyToggled(broadcast_only) => {
+                self.broadcast_only = broadcast_only;
+                self.audio_manager.backend_mut().set_local_playback(!broadcast_only);
+                self.save_settings();
+
+                Task::none()
+            }
+            Message::PlaybackModeSelected(playback_mode) => {
+                self.playback_mode = playback_mode;
+                for room in self.rooms.iter_mut() {
+                    room.set_playback_mode(playback_mode);
+                }
+                self.save_settings();
+
+                Task::none()
+            }
+            Message::UpdateCrossfadeOverlapSlider(overlap) => {
+                self.crossfade_overlap = overlap as u64;
+
+                Task::none()
+            }
+            Message::UpdateSfxVolumeSlider(volume) => {
+                self.sfx_volume = volume;
+                for room in self.rooms.iter_mut() {
+                    room.set_sfx_volume(volume);
+                }
+
+                Task::none()
+            }
+            Message::UpdateSfxPolyphonySlider(polyphony) => {
+                self.sfx_polyphony = polyphony as usize;
+                for room in self.rooms.iter_mut() {
+                    room.set_sfx_polyphony(self.sfx_polyphony);
+                }
+
+                Task::none()
+            }
+            Message::NormalizeLoudnessToggled(normalize) => {
+                self.normalize_loudness = normalize;
+                let target = self.normalize_to();
+                for room in self.rooms.iter_mut() {
+                    room.set_normalization(target);
+                }
+                self.save_settings();
+
+                Task::none()
+            }
+            Message::UpdateTargetLoudnessSlider(target) => {
+                self.target_loudness = target;
+                let target = self.normalize_to();
+                for room in self.rooms.iter_mut() {
+                    room.set_normalization(target);
+                }
+
+                Task::none()
+            }
+            Message::LoudnessAnalyzed(path, loudness) => {
+                if loudness.is_none() {
+                    println!("Couldn't measure the loudness of {}", path);
+                }
+                for room in self.rooms.iter_mut() {
+                    room.set_loudness(&path, loudness);
+                }
+
+                Task::none()
+            }
+            Message::ToggleStreamQuality => {
+                self.show_stream_quality = !self.show_stream_quality;
+
+                Task::none()
+            }
+            Message::StreamQuality(StreamQualityMessage::SaveSettings) => {
+                self.save_settings();
+
+                Task::none()
+            }
+            Message::StreamQuality(message) => {
+                // The sliders save once they're let go
+                let save = !matches!(message, StreamQualityMessage::Bitrate(_) | StreamQualityMessage::Complexity(_));
+                if let StreamQualityMessage::Layout(channel_layout) = message {
+                    self.channel_layout = channel_layout;
+                    for room in self.rooms.iter() {
+                        room.set_layout(channel_layout);
+                    }
+                } else {
+                    update_quality(&mut self.stream_quality, message);
+                    for room in self.rooms.iter() {
+                        room.set_quality(self.stream_quality);
+                    }
+                }
+                if save {
+                    self.save_settings();
+                }
+
+                Task::none()
+            }
+            Message::ToggleTalkOver => {
+                self.show_talk_over = !self.show_talk_over;
+
+                Task::none()
+            }
+            Message::TalkOver(message) => {
+                match message {
+                    TalkOverMessage::MicrophoneToggled(true) => {
+                        match Microphone::open(self.talk_over.mode, self.talk_over.threshold) {
+                            Ok(microphone) => {
+                                for room in self.rooms.iter() {
+                                    room.set_overlay(Some(Box::new(microphone.receiver())));
+                                }
+                                self.microphone = Some(microphone);
+                                self.microphone_error = None;
+                            }
+                            Err(e) => {
+                                println!("Error opening microphone: {}", e);
+                                self.microphone_error = Some(e.to_string());
+                            }
+                        }
+                    }
+                    TalkOverMessage::MicrophoneToggled(false) => {
+                        for room in self.rooms.iter() {
+                            room.set_overlay(None);
+                        }
+                        self.microphone = None;
+                        self.talking = false;
+                    }
+                    TalkOverMessage::Talking(talking) => {
+                        self.talking = talking;
+                        if let Some(microphone) = &self.microphone {
+                            microphone.set_talking(talking);
+                        }
+                    }
+                    TalkOverMessage::SaveSettings => self.save_settings(),
+                    message => {
+                        // The sliders save once they're let go
+                        let save = matches!(message, TalkOverMessage::Mode(_));
+                        update_talk_over(&mut self.talk_over, message);
+                        if let Some(microphone) = &self.microphone {
+                            microphone.set_mode(self.talk_over.mode);
+                            microphone.set_threshold(self.talk_over.threshold);
+                        }
+                        if save {
+                            self.save_settings();
+                        }
+                    }
+                }
+
+                Task::none()
+            }
+            Message::RestartEncoder => {
+                self.room().restart_encoder();
+
+                Task::none()
+            }
+            Message::ToggleMixer => {
+                self.show_mixer = !self.show_mixer;
+
+                Task::none()
+            }
+            Message::ToggleEffects => {
+                self.show_effects = !self.show_effects;
+
+                Task::none()
+            }
+            Message::MasterEffectsChanged(effects) => {
+                self.room_mut().set_master_effects(effects);
+
+                Task::none()
+            }
+            Message::ToggleScenes => {
+                self.show_scenes = !self.show_scenes;
+
+                Task::none()
+            }
+            Message::Scenes(message) => {
+                match message {
+                    SceneMessage::NameChanged(name) => self.new_scene_name = name,
+                    SceneMessage::Save => {
+                        let name = self.new_scene_name.trim().to_string();
+                        if !name.is_empty() {
+                            self.room_mut().save_scene(name);
+                            self.new_scene_name.clear();
+                        }
+                    },
+                    SceneMessage::Recall(index) => {
+                        let transition = self.scene_transition;
+                        self.room_mut().recall_scene(index, transition);
+                    },
+                    SceneMessage::Remove(index) => self.room_mut().remove_scene(index),
+                    SceneMessage::Transition(transition) => self.scene_transition = transition,
+                    SceneMessage::SaveSettings => self.save_settings(),
+                }
+
+                Task::none()
+            }
+            Message::ToggleShortcuts => {
+                self.show_shortcuts = !self.show_shortcuts;
+                self.recording_shortcut = None;
+
+                Task::none()
+            }
+            Message::Shortcuts(message) => {
+                match message {
+                    ShortcutsMessage::Record(shortcut) => self.recording_shortcut = Some(shortcut),
+                    ShortcutsMessage::CancelRecording => self.recording_shortcut = None,
+                    ShortcutsMessage::Clear(shortcut) => self.shortcuts.set(shortcut, None),
+                    ShortcutsMessage::ResetDefaults => self.shortcuts = settings::Shortcuts::default(),
+                    ShortcutsMessage::NumberKeysToggled(on) => self.shortcuts.number_keys_play_tracks = on,
+                }
+                self.save_settings();
+
+                Task::none()
+            }
+            Message::KeyPressed(key, modifiers) => {
+                let Some(binding) = key_binding(&key, modifiers) else {
+                    return Task::none();
+                };
+                if let Some(shortcut) = self.recording_shortcut.take() {
+                    self.shortcuts.set(shortcut, Some(binding));
+                    self.save_settings();
+                    return Task::none();
+                }
+                if let Some(shortcut) = self.shortcuts.find(&binding) {
+                    return self.run_shortcut(shortcut);
+                }
+                match track_number(&binding) {
+                    Some(index) if self.shortcuts.number_keys_play_tracks => self.play_track(index, false),
+                    _ => Task::none(),
+                }
+            }
+            Message::Server => {
+                self.server_running = false;
+
+                Task::none()
+            }
+        }
+    }
+
+    fn run_shortcut(&mut self, shortcut: settings::Shortcut) -> Task<Message> {
+        let current_track = self.room().playlist.current_track;
+        match shortcut {
+            settings::Shortcut::PlayPause => match self.room().playback_state() {
+                Some(PlaybackState::Playing) => self.update(Message::Pause),
+                Some(PlaybackState::Paused) => self.update(Message::Resume),
+                _ => self.play_track(current_track.unwrap_or(0), false),
+            },
+            settings::Shortcut::Stop => {
+                self.room_mut().stop(0);
+
+                Task::none()
+            },
+            settings::Shortcut::FadeOut => self.update(Message::Stop),
+            settings::Shortcut::NextTrack => self.play_track(current_track.map_or(0, |current| current + 1), true),
+            settings::Shortcut::PreviousTrack => match current_track {
+                Some(current) if current > 0 => self.play_track(current - 1, true),
+                _ => Task::none(),
+            },
+            settings::Shortcut::VolumeUp => self.nudge_volume(VOLUME_STEP),
+            settings::Shortcut::VolumeDown => self.nudge_volume(-VOLUME_STEP),
+        }
+    }
+
+    fn play_track(&mut self, index: usize, reset: bool) -> Task<Message> {
+        if index >= self.room().playlist.tracks.len() {
+            return Task::none();
+        }
+        self.update(Message::MultiplayerPlaylist(
+            MultiplayerPlaylistMessage::MultiplayerTrack(index, MultiplayerTrackMessage::Play(reset))
+        ))
+    }
+
+    /// Changes the current track's volume, as if its slider had been moved.
+    fn nudge_volume(&mut self, step: f64) -> Task<Message> {
+        let Some(track) = self.room().playlist.get_current_track() else {
+            return Task::none();
+        };
+        let volume = (track.volume + step).clamp(0.0, 1.0);
+        let index = self.room().playlist.current_track.unwrap();
+        self.update(Message::MultiplayerPlaylist(
+            MultiplayerPlaylistMessage::MultiplayerTrack(index, MultiplayerTrackMessage::UpdateVolumeSlider(volume))
+        ))
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let fade_in_slider: Container<'_, Message> = container(
+            row![
+                slider(
+                    0.0..=5000.0,
+                    self.fade_in_duration as f64,
+                    Message::UpdateFadeInDurationSlider,
+                )
+                    .on_release(Message::SaveSettings)
+                    .height(8)
+                    .width(FillPortion(4)),
+                text(format!("{} ms", self.fade_in_duration)).width(FillPortion(1)),
+            ]
+                .spacing(4)
+        )
+            .center_x(Fill)
+            .padding([6, 40]);
+        let fade_out_slider: Container<'_, Message> = container(
+            row![
+                slider(
+                    0.0..=5000.0,
+                    self.fade_out_duration as f64,
+                    Message::UpdateFadeOutDurationSlider,
+                )
+                    .on_release(Message::SaveSettings)
+                    .height(8)
+                    .width(FillPortion(4)),
+                text(format!("{} ms", self.fade_out_duration)).width(FillPortion(1)),
+            ]
+                .spacing(4)
+        )
+            .center_x(Fill)
+            .padding([6, 40]);
+
+        let room = self.room();
+
+        let room_tabs = Row::from_vec(
+            self.rooms.iter()
+                .enumerate()
+                .map(|(index, room)| {
+                    button(text(room.name.clone()))
+                        .style(if index == self.selected_room { button::primary } else { button::secondary })
+                        .on_press(Message::SelectRoom(index))
+                        .into()
+                })
+                .collect::<Vec<Element<Message>>>()
+        )
+            .spacing(4);
+        let room_controls = row![
+            room_tabs,
+            Space::with_width(Fill),
+            button("Stream quality")
+                .style(if self.show_stream_quality { button::primary } else { button::secondary })
+                .on_press(Message::ToggleStreamQuality),
+            button("Talk-over")
+                .style(if self.show_talk_over { button::primary } else { button::secondary })
+                .on_press(Message::ToggleTalkOver),
+            button("Mixer")
+                .style(if self.show_mixer { button::primary } else { button::secondary })
+                .on_press(Message::ToggleMixer),
+            button("Effects")
+                .style(if self.show_effects { button::primary } else { button::secondary })
+                .on_press(Message::ToggleEffects),
+            button("Scenes")
+                .style(if self.show_scenes { button::primary } else { button::secondary })
+                .on_press(Message::ToggleScenes),
+            button("Shortcuts")
+                .style(if self.show_shortcuts { button::primary } else { button::secondary })
+                .on_press(Message::ToggleShortcuts),
+            text_input("New channel name", &self.new_room_name)
+                .on_input(Message::NewRoomNameChanged)
+                .on_submit(Message::AddRoom)
+                .width(200),
+            button("Add channel").on_press(Message::AddRoom),
+            button("Remove channel").on_press_maybe((self.rooms.len() > 1).then_some(Message::RemoveRoom)),
+            if self.local_playback {
+                Element::from(
+                    toggler(self.broadcast_only)
+                        .label("Broadcast only")
+                        .on_toggle(Message::BroadcastOnlyToggled)
+                )
+            } else {
+                text("No local playback").into()
+            },
+        ]
+            .padding([4, 8])
+            .spacing(4);
+
+        let connected_clients = Arc::clone(&self.connected_clients);
+        let clients = connected_clients.lock().unwrap();
+        let client_views = clients.values()
+            .filter(|client| client.channel == room.name)
+            .map(|client| {
+                Text::new(client.username.to_string())
+                    .size(16)
+                    .into()
+            }).collect::<Vec<Element<Message>>>();
+        let client_container = Scrollable::new(
+            Column::from_vec(client_views)
+        )
+            .spacing(2);
+        
+        let controls = row![
+            action(
+                open_file_icon(),
+                "Open file",
+                (!self.is_loading).then_some(Message::OpenFiles)
+            ),
+            action(
+                save_icon(),
+                "Save playlist",
+                (!self.is_loading).then_some(Message::ExportPlaylist)
+            ),
+            action(
+                open_icon(),
+                "Open playlist",
+                (!self.is_loading).then_some(Message::ImportPlaylist)
+            ),
+            column![
+                fade_in_slider.align_y(Alignment::Start),
+                vertical_space(),
+                fade_out_slider.align_y(Alignment::End),
+            ].width(FillPortion(4)),
+            text("Connected clients:")
+                .align_x(Horizontal::Left)
+                .width(FillPortion(2)),
+            client_container.width(FillPortion(2)),
+        ]
+            .height(84)
+            .padding(4)
+            .spacing(2);
+
+        let total_duration = match room.playlist.get_current_track() {
+            Some(track) => track.data.duration(),
+            None => Duration::from_secs(0)
+        };
+        let seeker_slider: Container<'_, Message> = container(
+            slider(
+                0.0..=total_duration.as_secs_f64(),
+                room.playback_position,
+                Message::UpdatePlaybackPositionSlider,
+            )
+                .on_release(Message::SeekToPlaybackPosition)
+                .height(16)
+                .width(Fill)
+        )
+            .center_x(Fill)
+            .padding([10, 40]);
+
+        let playback_state = room.playback_state();
+        let playback_controls = row![
+            action(
+                open_icon(),
+                "Pause",
+                (playback_state == Some(PlaybackState::Playing)).then_some(Message::Pause)
+            ),
+            action(
+                save_icon(),
+                "Resume",
+                (playback_state == Some(PlaybackState::Paused)).then_some(Message::Resume)
+            ),
+            action(
+                open_icon(),
+                "Stop",
+                playback_state.is_some_and(|state| state != PlaybackState::Stopped).then_some(Message::Stop)
+            ),
+            pick_list(settings::PlaybackMode::ALL, Some(self.playback_mode), Message::PlaybackModeSelected)
+                .text_size(14),
+        ]
+            .push_maybe(
+                matches!(
+                    self.playback_mode,
+                    settings::PlaybackMode::Advance | settings::PlaybackMode::RepeatPlaylist | settings::PlaybackMode::Shuffle
+                )
+                    .then(|| {
+                        row![
+                            text("Overlap").size(14),
+                            slider(0.0..=15000.0, self.crossfade_overlap as f64, Message::UpdateCrossfadeOverlapSlider)
+                                .step(100.0)
+                                .on_release(Message::SaveSettings)
+                                .width(100),
+                            text(format!("{:.1} s", self.crossfade_overlap as f64 / 1000.0)).size(14),
+                        ]
+                            .align_y(Alignment::Center)
+                            .spacing(4)
+                    })
+            )
+            .push(
+                toggler(self.normalize_loudness)
+                    .label("Normalize loudness")
+                    .text_size(14)
+                    .on_toggle(Message::NormalizeLoudnessToggled)
+            )
+            .push_maybe(
+                self.normalize_loudness.then(|| {
+                    row![
+                        slider(-30.0..=-10.0, self.target_loudness, Message::UpdateTargetLoudnessSlider)
+                            .step(0.5)
+                            .on_release(Message::SaveSettings)
+                            .width(100),
+                        text(format!("{:.1} LUFS", self.target_loudness)).size(14),
+                    ]
+                        .align_y(Alignment::Center)
+                        .spacing(4)
+                })
+            )
+            .height(36)
+            .padding(8)
+            .spacing(8);
+
+
+        column![
+            room_controls,
+        ]
+            .push_maybe(encoder_status_view(room.encoder_status()))
+            .push(controls)
+            .push_maybe(
+                self.show_stream_quality
+                    .then(|| quality_view(self.channel_layout, &self.stream_quality).map(Message::StreamQuality))
+            )
+            .push_maybe(
+                self.show_talk_over
+                    .then(|| talk_over_view(&self.talk_over, self.microphone.is_some(), self.microphone_error.as_deref()).map(Message::TalkOver))
+            )
+            .push_maybe(
+                self.show_shortcuts
+                    .then(|| shortcuts_view(&self.shortcuts, self.recording_shortcut).map(Message::Shortcuts))
+            )
+            .push_maybe(self.show_mixer.then(|| mixer_view(room)))
+            .push_maybe(
+                self.show_effects
+                    .then(|| effects_view("Master effects", room.playlist.master_effects, Message::MasterEffectsChanged))
+            )
+            .push_maybe(
+                self.show_scenes
+                    .then(|| scenes_view(&room.playlist.scenes, &self.new_scene_name, self.scene_transition).map(Message::Scenes))
+            )
+            .push(playlist_view(room))
+            .push_maybe(sfx_view(room, self.sfx_volume, self.sfx_polyphony))
+            .push(vertical_space())
+            .push(seeker_slider)
+            .push(
+                row![
+                    container(playback_controls).center_x(FillPortion(1)),
+                ]
+                    .push_maybe(
+                        (self.microphone.is_some() && self.talk_over.mode == settings::TalkMode::PushToTalk)
+                            .then(|| push_to_talk_button(self.talking).map(Message::TalkOver))
+                    )
+                    .push(container(meter_view(&self.meter)).width(FillPortion(1)))
+                    .align_y(Alignment::Center)
+            )
+            .into()
+    }
+}
+
+/// Only shown while the room's stream isn't running normally.
+fn encoder_status_view<'a>(status: EncoderStatus) -> Option<Element<'a, Message>> {
+    let (color, retry) = match status {
+        EncoderStatus::Running | EncoderStatus::Stopped => return None,
+        EncoderStatus::Starting => (Color::from_rgb(0.6, 0.6, 0.6), false),
+        EncoderStatus::Restarting { .. } => (Color::from_rgb(0.9, 0.6, 0.1), false),
+        EncoderStatus::Failed(_) => (Color::from_rgb(0.9, 0.1, 0.1), true),
+    };
+    Some(
+        row![
+            text(format!("Stream: {}", status)).color(color).width(Fill),
+        ]
+            .push_maybe(retry.then(|| button("Retry").on_press(Message::RestartEncoder)))
+            .align_y(Alignment::Center)
+            .padding([4, 8])
+            .spacing(8)
+            .into()
+    )
+}
+
+async fn open_files() -> Result<Vec<MultiplayerTrack>, Error> {
+    let paths = rfd::AsyncFileDialog::new()
+        .set_title("Choose an audio file...")
+        .add_filter("Audio files", &["wav", "mp3", "flac", "ogg"])
+        .pick_files()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    paths.iter().map(|path| {
+        MultiplayerTrack::new(String::from(path.path().to_str().unwrap()))
+    })
+    .collect::<Result<Vec<MultiplayerTrack>, Error>>()
+
+}
+
+/// The integrated loudness of `data`, measured on a blocking thread since it goes through every frame.
+async fn measure_loudness(data: SoundData) -> Option<Measurement> {
+    tokio::task::spawn_blocking(move || match data {
+        SoundData::Static(data) => loudness::measure(&data.frames, data.sample_rate),
+        SoundData::Streaming { path, .. } => measure_streamed_loudness(&path),
+    })
+        .await
+        .unwrap_or(None)
+}
+
+/// Decodes the file a bit at a time, since streamed files are the ones too long to hold in memory.
+fn measure_streamed_loudness(path: &str) -> Option<Measurement> {
+    let decoder = rodio::Decoder::try_from(std::fs::File::open(path).ok()?).ok()?;
+    let channels = decoder.channels() as usize;
+    let mut meter = loudness::LoudnessMeter::new(decoder.sample_rate());
+    let mut samples = Vec::with_capacity(channels);
+    for sample in decoder {
+        samples.push(sample);
+        if samples.len() == channels {
+            // Mono plays in both channels, anything past stereo isn't heard
+            meter.push(Frame::new(samples[0], samples[channels.min(2) - 1]));
+            samples.clear();
+        }
+    }
+    meter.finish()
+}
+
+async fn open_playlist() -> Result<MultiplayerPlaylist, Error> {
+    let path = rfd::AsyncFileDialog::new()
+        .set_title("Choose a playlist file...")
+        .add_filter("Playlist files", &["json"])
+        .pick_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    parse_playlist(path).await
+}
+
+async fn parse_playlist(file_handle: FileHandle) -> Result<MultiplayerPlaylist, Error> {
+    let playlist_json = std::fs::read_to_string(file_handle.path().to_str().unwrap()).unwrap();
+    let playlist: Playlist = serde_json::from_str(&playlist_json).unwrap();
+    
+    MultiplayerPlaylist::from(&playlist)
+}
+
+async fn save_playlist() -> Result<FileHandle, Error> {
+    let path = rfd::AsyncFileDialog::new()
+        .set_title("Choose a playlist file...")
+        .add_filter("Playlist files", &["json"])
+        .save_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    Ok(path)
+}
+
+async fn save_playlist_to_file(path: FileHandle, playlist_json: String) -> Result<(), Error> {
+    let res = path.write(playlist_json.as_bytes()).await;
+    match res {
+        Ok(_) => {
+            Ok(())
+        }
+        Err(_) => {
+            Err(Error::IoError(io::ErrorKind::Other))
+        }
+    }
+}
+
+fn action<'a, Message: Clone + 'a>(
+    content: impl Into<Element<'a, Message>>,
+    label: &'a str,
+    on_press: Option<Message>,
+) -> Element<'a, Message> {
+    let action = button(center(content).width(30));
+
+    if let Some(on_press) = on_press {
+        tooltip(
+            action.on_press(on_press),
+            label,
+            tooltip::Position::FollowCursor,
+        )
+            .style(container::rounded_box)
+            .into()
+    } else {
+        action.style(button::secondary).into()
+    }
+}
+
+fn open_file_icon<'a, Message>() -> Element<'a, Message> {
+    icon('\u{0e800}')
+}
+
+fn save_icon<'a, Message>() -> Element<'a, Message> {
+    icon('\u{0e801}')
+}
+
+fn open_icon<'a, Message>() -> Element<'a, Message> {
+    icon('\u{0f115}')
+}
+
+fn icon<'a, Message>(codepoint: char) -> Element<'a, Message> {
+    const ICON_FONT: Font = Font::with_name("editor-icons");
+
+    text(codepoint).font(ICON_FONT).into()
+}
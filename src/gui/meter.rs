@@ -0,0 +1,81 @@
+use iced::widget::{column, container, progress_bar, row, text, Column};
+use iced::{Color, Element, Fill};
+use multiplayer::meter::{to_decibels, Levels};
+use std::time::{Duration, Instant};
+
+pub const METER_INTERVAL: Duration = Duration::from_millis(50);
+const CLIP_HOLD: Duration = Duration::from_secs(3);
+// Lowest level the bars show
+const FLOOR_DB: f32 = -60.0;
+// Peaks fall back by this much per update instead of dropping at once
+const PEAK_FALL_DB: f32 = 1.5;
+
+/// What the meters show: falling peaks, the latest RMS, and when the clip indicator goes out.
+#[derive(Debug, Default)]
+pub struct MeterDisplay {
+    peak: Vec<f32>,
+    rms: Vec<f32>,
+    clip_until: Option<Instant>,
+}
+
+impl MeterDisplay {
+    pub fn update(&mut self, levels: Levels) {
+        if self.peak.len() != levels.peak.len() {
+            self.peak = vec![FLOOR_DB; levels.peak.len()];
+        }
+        for (shown, peak) in self.peak.iter_mut().zip(&levels.peak) {
+            *shown = to_decibels(*peak).max(*shown - PEAK_FALL_DB);
+        }
+        self.rms = levels.rms.iter().map(|rms| to_decibels(*rms)).collect();
+        if levels.clipped {
+            self.clip_until = Some(Instant::now() + CLIP_HOLD);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn clipping(&self) -> bool {
+        self.clip_until.is_some_and(|until| Instant::now() < until)
+    }
+}
+
+fn bar_value(db: f32) -> f32 {
+    (db - FLOOR_DB).max(0.0)
+}
+
+pub fn meter_view<'a, Message: 'a>(display: &MeterDisplay) -> Element<'a, Message> {
+    let bars = Column::from_vec(
+        display.peak.iter()
+            .zip(&display.rms)
+            .map(|(peak, rms)| {
+                column![
+                    progress_bar(0.0..=-FLOOR_DB, bar_value(*peak)).height(4),
+                    progress_bar(0.0..=-FLOOR_DB, bar_value(*rms)).height(6),
+                ]
+                    .spacing(1)
+                    .into()
+            })
+            .collect::<Vec<Element<'a, Message>>>()
+    )
+        .spacing(3)
+        .width(Fill);
+    let loudest = display.peak.iter().cloned().fold(FLOOR_DB, f32::max);
+    let clip = text("CLIP").color(if display.clipping() {
+        Color::from_rgb(0.9, 0.1, 0.1)
+    } else {
+        Color::from_rgba(0.5, 0.5, 0.5, 0.4)
+    });
+
+    container(
+        row![
+            bars,
+            text(format!("{:.0} dB", loudest)).width(56),
+            clip,
+        ]
+            .spacing(8)
+    )
+        .padding([4, 40])
+        .into()
+}
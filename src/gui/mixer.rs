@@ -0,0 +1,47 @@
+use super::track::format_time;
+use crate::gui::host::Message;
+use iced::widget::{button, column, container, row, slider, text, Column};
+use iced::{Element, Fill, FillPortion};
+use multiplayer::host::room::Room;
+use multiplayer::host::track::{MultiplayerPlaylistMessage, MultiplayerTrackMessage};
+use std::path::Path;
+
+/// Everything playing in the room, the current track and the layers on top of it, each with its own volume.
+pub fn mixer_view(room: &Room) -> Element<'_, Message> {
+    let layers = room.active_layers()
+        .filter_map(|layer| {
+            let index = layer.index();
+            let track = room.playlist.get_track(index)?;
+            let name = Path::new(&track.path)
+                .file_name()
+                .map_or(track.path.clone(), |name| name.to_string_lossy().to_string());
+            let strip: Element<MultiplayerTrackMessage> = row![
+                text(name).width(FillPortion(2)),
+                text(if layer.is_main() { "Playlist" } else { "Layer" }).width(FillPortion(1)),
+                slider(0.0..=1.0, track.volume, MultiplayerTrackMessage::UpdateVolumeSlider)
+                    .step(0.01)
+                    .width(FillPortion(3)),
+                text(format_time(layer.position())).width(FillPortion(1)),
+                button("Stop").on_press(MultiplayerTrackMessage::ToggleLayer),
+            ]
+                .spacing(8)
+                .into();
+            Some(strip.map(move |message| Message::MultiplayerPlaylist(MultiplayerPlaylistMessage::MultiplayerTrack(index, message))))
+        })
+        .collect::<Vec<Element<Message>>>();
+
+    let content = if layers.is_empty() {
+        column![text("Mixer").size(18), text("Nothing is playing")]
+    } else {
+        column![
+            text("Mixer").size(18),
+            Column::with_children(layers).spacing(4),
+            button("Stop all").on_press(Message::StopAll),
+        ]
+    };
+
+    container(content.spacing(6))
+        .width(Fill)
+        .padding([6, 40])
+        .into()
+}
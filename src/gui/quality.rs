@@ -0,0 +1,80 @@
+use iced::widget::{checkbox, column, container, pick_list, row, slider, text};
+use iced::{Element, Fill, FillPortion};
+use multiplayer::multistream::{Application, Bandwidth, FrameDuration};
+use multiplayer::protocol::ChannelLayout;
+use multiplayer::settings::StreamQuality;
+
+#[derive(Debug, Clone)]
+pub enum StreamQualityMessage {
+    Layout(ChannelLayout),
+    Bitrate(u32),
+    FrameDuration(FrameDuration),
+    Complexity(u8),
+    Vbr(bool),
+    Bandwidth(Bandwidth),
+    Application(Application),
+    /// Sent when a slider is let go. Sliders save the settings then, not on every step they're dragged through.
+    SaveSettings,
+}
+
+/// Applies everything but `Layout`, which lives next to the quality in the settings.
+pub fn update_quality(quality: &mut StreamQuality, message: StreamQualityMessage) {
+    match message {
+        StreamQualityMessage::Layout(_) | StreamQualityMessage::SaveSettings => {}
+        StreamQualityMessage::Bitrate(bitrate) => quality.bitrate = bitrate,
+        StreamQualityMessage::FrameDuration(frame_duration) => quality.frame_duration = frame_duration,
+        StreamQualityMessage::Complexity(complexity) => quality.complexity = complexity,
+        StreamQualityMessage::Vbr(vbr) => quality.vbr = vbr,
+        StreamQualityMessage::Bandwidth(bandwidth) => quality.bandwidth = bandwidth,
+        StreamQualityMessage::Application(application) => quality.application = application,
+    }
+}
+
+fn labeled<'a>(label: &'a str, control: impl Into<Element<'a, StreamQualityMessage>>) -> Element<'a, StreamQualityMessage> {
+    row![
+        text(label).width(FillPortion(1)),
+        container(control).width(FillPortion(3)),
+    ]
+        .spacing(8)
+        .into()
+}
+
+pub fn quality_view(layout: ChannelLayout, quality: &StreamQuality) -> Element<'_, StreamQualityMessage> {
+    container(
+        column![
+            text("Stream quality").size(18),
+            labeled("Channels", pick_list(ChannelLayout::ALL, Some(layout), StreamQualityMessage::Layout)),
+            labeled(
+                "Bitrate",
+                row![
+                    slider(6.0..=256.0, quality.bitrate as f64, |bitrate| StreamQualityMessage::Bitrate(bitrate as u32))
+                        .on_release(StreamQualityMessage::SaveSettings)
+                        .width(FillPortion(4)),
+                    text(format!("{} kbps per channel", quality.bitrate)).width(FillPortion(2)),
+                ]
+                    .spacing(4)
+            ),
+            labeled(
+                "Frame duration",
+                pick_list(FrameDuration::ALL, Some(quality.frame_duration), StreamQualityMessage::FrameDuration)
+            ),
+            labeled(
+                "Complexity",
+                row![
+                    slider(0.0..=10.0, quality.complexity as f64, |complexity| StreamQualityMessage::Complexity(complexity as u8))
+                        .on_release(StreamQualityMessage::SaveSettings)
+                        .width(FillPortion(4)),
+                    text(quality.complexity.to_string()).width(FillPortion(2)),
+                ]
+                    .spacing(4)
+            ),
+            labeled("Bitrate mode", checkbox("Variable", quality.vbr).on_toggle(StreamQualityMessage::Vbr)),
+            labeled("Bandwidth", pick_list(Bandwidth::ALL, Some(quality.bandwidth), StreamQualityMessage::Bandwidth)),
+            labeled("Tuned for", pick_list(Application::ALL, Some(quality.application), StreamQualityMessage::Application)),
+        ]
+            .spacing(6)
+    )
+        .width(Fill)
+        .padding([6, 40])
+        .into()
+}
@@ -0,0 +1,58 @@
+use iced::widget::{button, column, container, row, slider, text, text_input, Column};
+use iced::{Element, Fill, FillPortion};
+use multiplayer::host::playlist::Scene;
+
+#[derive(Debug, Clone)]
+pub enum SceneMessage {
+    NameChanged(String),
+    /// Saves what's playing under the typed name.
+    Save,
+    Recall(usize),
+    Remove(usize),
+    Transition(u64),
+    /// Sent when a slider is let go. Sliders save the settings then, not on every step they're dragged through.
+    SaveSettings,
+}
+
+pub fn scenes_view<'a>(scenes: &'a [Scene], new_scene_name: &'a str, transition: u64) -> Element<'a, SceneMessage> {
+    let scene_rows = scenes.iter()
+        .enumerate()
+        .map(|(index, scene)| {
+            row![
+                text(&scene.name).width(FillPortion(2)),
+                text(format!("{} tracks", scene.layers.len())).width(FillPortion(1)),
+                button("Recall").on_press(SceneMessage::Recall(index)),
+                button("Remove").on_press(SceneMessage::Remove(index)),
+            ]
+                .spacing(8)
+                .into()
+        })
+        .collect::<Vec<Element<SceneMessage>>>();
+
+    let content = column![
+        text("Scenes").size(18),
+        Column::with_children(scene_rows).spacing(4),
+        row![
+            text_input("Scene name", new_scene_name)
+                .on_input(SceneMessage::NameChanged)
+                .on_submit(SceneMessage::Save),
+            button("Save what's playing").on_press_maybe((!new_scene_name.trim().is_empty()).then_some(SceneMessage::Save)),
+        ]
+            .spacing(8),
+        row![
+            text("Transition").width(FillPortion(1)),
+            container(
+                slider(0.0..=20000.0, transition as f64, |transition| SceneMessage::Transition(transition as u64))
+                    .step(100.0)
+                    .on_release(SceneMessage::SaveSettings)
+            ).width(FillPortion(2)),
+            text(format!("{} ms", transition)).width(FillPortion(1)),
+        ]
+            .spacing(8),
+    ];
+
+    container(content.spacing(6))
+        .width(Fill)
+        .padding([6, 40])
+        .into()
+}
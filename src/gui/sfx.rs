@@ -0,0 +1,53 @@
+use crate::gui::host::Message;
+use iced::widget::{button, column, container, row, slider, text, Row};
+use iced::{Element, Fill, FillPortion};
+use multiplayer::host::room::Room;
+use multiplayer::host::track::{MultiplayerPlaylistMessage, MultiplayerTrackMessage};
+use std::path::Path;
+
+const MAX_POLYPHONY: usize = 32;
+
+/// A button for every track marked as a sound effect, below the playlist. Nothing if there are none.
+pub fn sfx_view(room: &Room, volume: f64, polyphony: usize) -> Option<Element<'_, Message>> {
+    let buttons = room.playlist.tracks.iter()
+        .enumerate()
+        .filter(|(_, track)| track.sfx)
+        .map(|(index, track)| {
+            let name = Path::new(&track.path)
+                .file_stem()
+                .map_or(track.path.clone(), |name| name.to_string_lossy().to_string());
+            button(text(name))
+                .padding([10, 16])
+                .on_press(Message::MultiplayerPlaylist(
+                    MultiplayerPlaylistMessage::MultiplayerTrack(index, MultiplayerTrackMessage::Play(false))
+                ))
+                .into()
+        })
+        .collect::<Vec<Element<Message>>>();
+    if buttons.is_empty() {
+        return None;
+    }
+
+    let controls = row![
+        text("Sound effects").size(18).width(FillPortion(2)),
+        text("Volume").width(FillPortion(1)),
+        slider(0.0..=1.0, volume, Message::UpdateSfxVolumeSlider)
+            .step(0.01)
+            .on_release(Message::SaveSettings)
+            .width(FillPortion(2)),
+        text("At once").width(FillPortion(1)),
+        slider(1.0..=MAX_POLYPHONY as f64, polyphony as f64, Message::UpdateSfxPolyphonySlider)
+            .step(1.0)
+            .on_release(Message::SaveSettings)
+            .width(FillPortion(2)),
+        text(polyphony.to_string()).width(FillPortion(1)),
+    ]
+        .spacing(8);
+
+    Some(
+        container(column![controls, Row::with_children(buttons).spacing(6).wrap()].spacing(6))
+            .width(Fill)
+            .padding([6, 40])
+            .into()
+    )
+}
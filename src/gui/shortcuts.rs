@@ -0,0 +1,117 @@
+use iced::keyboard::key::Named;
+use iced::keyboard::{Key, Modifiers};
+use iced::widget::{button, column, container, row, text, toggler};
+use iced::{Element, Fill, FillPortion};
+use multiplayer::settings::{KeyBinding, Shortcut, Shortcuts};
+
+#[derive(Debug, Clone)]
+pub enum ShortcutsMessage {
+    /// Binds the next key pressed to the shortcut.
+    Record(Shortcut),
+    CancelRecording,
+    Clear(Shortcut),
+    ResetDefaults,
+    NumberKeysToggled(bool),
+}
+
+/// The binding for a key press, if it's a key that can be bound. Modifiers on their own can't,
+/// they're only ever held down along with another key.
+pub fn key_binding(key: &Key, modifiers: Modifiers) -> Option<KeyBinding> {
+    let key = match key {
+        Key::Character(character) => character.to_lowercase(),
+        Key::Named(Named::Control | Named::Shift | Named::Alt | Named::Super) => return None,
+        Key::Named(named) => format!("{:?}", named),
+        Key::Unidentified => return None,
+    };
+    Some(KeyBinding {
+        key,
+        ctrl: modifiers.control(),
+        alt: modifiers.alt(),
+        shift: modifiers.shift(),
+    })
+}
+
+/// The track a number key plays, counting from zero.
+pub fn track_number(binding: &KeyBinding) -> Option<usize> {
+    if binding.ctrl || binding.alt || binding.shift {
+        return None;
+    }
+    match binding.key.parse::<usize>() {
+        Ok(number @ 1..=9) => Some(number - 1),
+        _ => None,
+    }
+}
+
+pub fn shortcuts_view<'a>(shortcuts: &Shortcuts, recording: Option<Shortcut>) -> Element<'a, ShortcutsMessage> {
+    let mut content = column![text("Shortcuts").size(18)];
+    for shortcut in Shortcut::ALL {
+        let binding = if recording == Some(shortcut) {
+            String::from("Press a key...")
+        } else {
+            shortcuts.get(shortcut).map_or(String::from("None"), KeyBinding::to_string)
+        };
+        let change = if recording == Some(shortcut) {
+            button("Cancel").on_press(ShortcutsMessage::CancelRecording)
+        } else {
+            button("Change").on_press(ShortcutsMessage::Record(shortcut))
+        };
+        content = content.push(row![
+            text(shortcut.to_string()).width(FillPortion(1)),
+            text(binding).width(FillPortion(1)),
+            change,
+            button("Clear").on_press_maybe(shortcuts.get(shortcut).is_some().then_some(ShortcutsMessage::Clear(shortcut))),
+        ]
+            .spacing(8));
+    }
+    content = content
+        .push(
+            toggler(shortcuts.number_keys_play_tracks)
+                .label("Number keys 1 to 9 play that track")
+                .on_toggle(ShortcutsMessage::NumberKeysToggled)
+        )
+        .push(button("Reset to defaults").on_press(ShortcutsMessage::ResetDefaults));
+
+    container(content.spacing(6))
+        .width(Fill)
+        .padding([6, 40])
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(key: &str) -> KeyBinding {
+        KeyBinding {
+            key: key.to_string(),
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    #[test]
+    fn modifiers_alone_are_not_bindings() {
+        for named in [Named::Control, Named::Shift, Named::Alt, Named::Super] {
+            assert_eq!(key_binding(&Key::Named(named), Modifiers::CTRL), None);
+        }
+    }
+
+    #[test]
+    fn keys_are_bound_with_their_modifiers() {
+        let bound = key_binding(&Key::Character("F".into()), Modifiers::CTRL | Modifiers::SHIFT).unwrap();
+        assert_eq!(bound, KeyBinding { ctrl: true, shift: true, ..binding("f") });
+        assert_eq!(key_binding(&Key::Named(Named::Space), Modifiers::empty()), Some(binding("Space")));
+        assert_eq!(key_binding(&Key::Unidentified, Modifiers::empty()), None);
+    }
+
+    #[test]
+    fn number_keys_count_from_zero() {
+        assert_eq!(track_number(&binding("1")), Some(0));
+        assert_eq!(track_number(&binding("9")), Some(8));
+        assert_eq!(track_number(&binding("0")), None);
+        assert_eq!(track_number(&binding("10")), None);
+        assert_eq!(track_number(&binding("a")), None);
+        assert_eq!(track_number(&KeyBinding { alt: true, ..binding("1") }), None);
+    }
+}
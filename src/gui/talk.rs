@@ -0,0 +1,90 @@
+use iced::widget::{column, container, mouse_area, pick_list, row, slider, text, toggler};
+use iced::{Element, Fill, FillPortion};
+use multiplayer::settings::{TalkMode, TalkOver};
+
+#[derive(Debug, Clone)]
+pub enum TalkOverMessage {
+    MicrophoneToggled(bool),
+    Talking(bool),
+    Mode(TalkMode),
+    Threshold(f32),
+    DuckDepth(f32),
+    Release(u64),
+    /// Sent when a slider is let go. Sliders save the settings then, not on every step they're dragged through.
+    SaveSettings,
+}
+
+/// Applies the messages that change settings, the rest need the microphone itself.
+pub fn update_talk_over(talk_over: &mut TalkOver, message: TalkOverMessage) {
+    match message {
+        TalkOverMessage::MicrophoneToggled(_) | TalkOverMessage::Talking(_) | TalkOverMessage::SaveSettings => {}
+        TalkOverMessage::Mode(mode) => talk_over.mode = mode,
+        TalkOverMessage::Threshold(threshold) => talk_over.threshold = threshold,
+        TalkOverMessage::DuckDepth(duck_depth) => talk_over.duck_depth = duck_depth,
+        TalkOverMessage::Release(release) => talk_over.release = release,
+    }
+}
+
+fn labeled<'a>(label: &'a str, control: impl Into<Element<'a, TalkOverMessage>>, value: String) -> Element<'a, TalkOverMessage> {
+    row![
+        text(label).width(FillPortion(1)),
+        container(control).width(FillPortion(2)),
+        text(value).width(FillPortion(1)),
+    ]
+        .spacing(8)
+        .into()
+}
+
+pub fn talk_over_view<'a>(talk_over: &TalkOver, microphone_on: bool, error: Option<&'a str>) -> Element<'a, TalkOverMessage> {
+    let mut content = column![
+        text("Talk-over").size(18),
+        toggler(microphone_on)
+            .label("Microphone")
+            .on_toggle(TalkOverMessage::MicrophoneToggled),
+    ]
+        .push_maybe(error.map(text))
+        .push(row![
+            text("Mode").width(FillPortion(1)),
+            container(pick_list(TalkMode::ALL, Some(talk_over.mode), TalkOverMessage::Mode)).width(FillPortion(3)),
+        ]
+            .spacing(8));
+    if talk_over.mode == TalkMode::VoiceActivation {
+        content = content.push(labeled(
+            "Threshold",
+            slider(-70.0..=0.0, talk_over.threshold, TalkOverMessage::Threshold).on_release(TalkOverMessage::SaveSettings),
+            format!("{:.0} dB", talk_over.threshold),
+        ));
+    }
+    content = content
+        .push(labeled(
+            "Duck depth",
+            slider(-40.0..=0.0, talk_over.duck_depth, TalkOverMessage::DuckDepth).on_release(TalkOverMessage::SaveSettings),
+            format!("{:.0} dB", talk_over.duck_depth),
+        ))
+        .push(labeled(
+            "Release",
+            slider(0.0..=5000.0, talk_over.release as f64, |release| TalkOverMessage::Release(release as u64))
+                .on_release(TalkOverMessage::SaveSettings),
+            format!("{} ms", talk_over.release),
+        ));
+
+    container(content.spacing(6))
+        .width(Fill)
+        .padding([6, 40])
+        .into()
+}
+
+/// Keeps the microphone open for as long as it's held down.
+/// A plain container so the mouse area sees the release, a button would capture it.
+/// The mouse area misses releases outside of it, so dragging off the button lets go too.
+pub fn push_to_talk_button<'a>(talking: bool) -> Element<'a, TalkOverMessage> {
+    mouse_area(
+        container(text(if talking { "Talking..." } else { "Hold to talk" }))
+            .padding([5, 10])
+            .style(if talking { container::dark } else { container::rounded_box })
+    )
+        .on_press(TalkOverMessage::Talking(true))
+        .on_release(TalkOverMessage::Talking(false))
+        .on_exit(TalkOverMessage::Talking(false))
+        .into()
+}
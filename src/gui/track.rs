@@ -0,0 +1,212 @@
+use iced::alignment::Horizontal;
+use iced::widget::{button, column, container, pick_list, row, scrollable, slider, text, text_input, toggler, Column, Container, Row};
+use iced::{Element, Fill};
+use iced::Length;
+use multiplayer::host::playlist::{Fade, FadeCurve};
+use multiplayer::host::room::Room;
+use multiplayer::host::track::{MultiplayerPlaylistMessage, MultiplayerTrack, MultiplayerTrackMessage};
+use crate::gui::effects::effects_view;
+use crate::gui::host::Message;
+
+pub fn format_time(seconds: f64) -> String {
+    format!("{}:{:04.1}", (seconds / 60.0) as u64, seconds % 60.0)
+}
+
+/// Cue buttons jump to that point, starting the track if it isn't playing.
+fn cues_view(track: &MultiplayerTrack) -> Element<'_, MultiplayerTrackMessage> {
+    Row::with_children(
+        track.cues.iter()
+            .enumerate()
+            .map(|(index, cue)| {
+                let jump = button(text(format!("{} ({})", cue.name, format_time(cue.position))))
+                    .on_press(MultiplayerTrackMessage::JumpToCue(index));
+                if track.editing {
+                    row![jump, button("x").on_press(MultiplayerTrackMessage::RemoveCue(index))].into()
+                } else {
+                    jump.into()
+                }
+            })
+    )
+        .spacing(4)
+        .wrap()
+        .into()
+}
+
+/// A fade override, off to use the host's fade.
+fn fade_view<'a>(label: &'a str, fade: Option<Fade>, on_change: fn(Option<Fade>) -> MultiplayerTrackMessage) -> Element<'a, MultiplayerTrackMessage> {
+    let mut content = row![
+        text(label).width(80),
+        toggler(fade.is_some()).on_toggle(move |on| on_change(on.then(Fade::default))).width(40),
+    ]
+        .spacing(8);
+    if let Some(fade) = fade {
+        content = content
+            .push(pick_list(FadeCurve::ALL, Some(fade.curve), move |curve| on_change(Some(Fade { curve, ..fade }))))
+            .push(
+                slider(0.0..=10000.0, fade.duration as f64, move |duration| on_change(Some(Fade { duration: duration as u64, ..fade })))
+                    .step(100.0)
+                    .width(Fill)
+            )
+            .push(text(format!("{} ms", fade.duration)).width(60));
+    } else {
+        content = content.push(text("Host's fade").width(Fill));
+    }
+    content.into()
+}
+
+/// Trim, fades, loop points and cues. The "Here" buttons only work while this track plays.
+fn editor_view(track: &MultiplayerTrack) -> Element<'_, MultiplayerTrackMessage> {
+    let duration = track.duration();
+    let loop_start = track.loop_start.unwrap_or(track.start_offset);
+    let loop_end = track.loop_end.unwrap_or(track.end());
+
+    column![
+        row![
+            text("Start at").width(80),
+            slider(0.0..=duration, track.start_offset, MultiplayerTrackMessage::StartOffsetChanged).step(0.01).width(Fill),
+            text(format_time(track.start_offset)).width(60),
+        ]
+            .spacing(8),
+        row![
+            text("End at").width(80),
+            slider(0.0..=duration, track.end(), MultiplayerTrackMessage::TrimEndChanged).step(0.01).width(Fill),
+            text(format_time(track.end())).width(60),
+        ]
+            .spacing(8),
+        fade_view("Fade in", track.fade_in, MultiplayerTrackMessage::FadeInChanged),
+        fade_view("Fade out", track.fade_out, MultiplayerTrackMessage::FadeOutChanged),
+        effects_view("Effects", track.effects, MultiplayerTrackMessage::EffectsChanged),
+        row![
+            text("Loop from").width(80),
+            slider(0.0..=duration, loop_start, MultiplayerTrackMessage::LoopStartChanged).step(0.01).width(Fill),
+            text(format_time(loop_start)).width(60),
+            button("Here").on_press(MultiplayerTrackMessage::SetLoopStartHere),
+        ]
+            .spacing(8),
+        row![
+            text("Loop to").width(80),
+            slider(0.0..=duration, loop_end, MultiplayerTrackMessage::LoopEndChanged).step(0.01).width(Fill),
+            text(format_time(loop_end)).width(60),
+            button("Here").on_press(MultiplayerTrackMessage::SetLoopEndHere),
+        ]
+            .spacing(8),
+        row![
+            button("Clear loop").on_press_maybe(
+                (track.loop_start.is_some() || track.loop_end.is_some()).then_some(MultiplayerTrackMessage::ClearLoop)
+            ),
+            text_input("Cue name", &track.new_cue_name)
+                .on_input(MultiplayerTrackMessage::CueNameChanged)
+                .on_submit(MultiplayerTrackMessage::AddCue),
+            button("Add cue").on_press(MultiplayerTrackMessage::AddCue),
+        ]
+            .spacing(8),
+    ]
+        .spacing(6)
+        .padding([4, 40])
+        .into()
+}
+
+fn loudness_label(track: &MultiplayerTrack) -> String {
+    match track.loudness {
+        Some(loudness) => format!("{:.1} LUFS", loudness),
+        None if track.analyzing => String::from("Analyzing..."),
+        None => String::from("-- LUFS"),
+    }
+}
+
+pub fn track_view(track: &MultiplayerTrack, currently_playing: bool, layered: bool) -> Element<MultiplayerTrackMessage> {
+    let audio_slider: Container<MultiplayerTrackMessage> = container(
+        slider(
+            0.0..=1.0,
+            track.volume,
+            MultiplayerTrackMessage::UpdateVolumeSlider,
+        )
+            .height(16)
+            .step(0.01)
+            .width(Fill)
+    )
+        .center_x(Fill)
+        .padding([10, 40]);
+
+    let top_row: Container<MultiplayerTrackMessage> = container(
+        row![
+            container(
+                button("Play").on_press(MultiplayerTrackMessage::Play(false)).height(32)
+            ).align_x(Horizontal::Left)
+            .padding([2, 4]),
+            container(
+                button("Reset").on_press(MultiplayerTrackMessage::Play(true)).height(32)
+            ).align_x(Horizontal::Left)
+            .padding([2, 4]),
+            container(
+                button("Layer")
+                    .style(if layered { button::primary } else { button::secondary })
+                    .on_press(MultiplayerTrackMessage::ToggleLayer)
+                    .height(32)
+            ).align_x(Horizontal::Left)
+            .padding([2, 4]),
+            container(
+                button("SFX")
+                    .style(if track.sfx { button::primary } else { button::secondary })
+                    .on_press(MultiplayerTrackMessage::ToggleSfx)
+                    .height(32)
+            ).align_x(Horizontal::Left)
+            .padding([2, 4]),
+            text(if track.data.is_streaming() { format!("{} (streamed)", track.path) } else { track.path.to_string() })
+                .align_x(Horizontal::Center)
+                .width(Fill),
+            text(loudness_label(track)).size(14).width(100),
+            container(
+                button(if track.editing { "Done" } else { "Edit" }).on_press(MultiplayerTrackMessage::ToggleEditor).height(32)
+            ).padding([2, 4]),
+            column![
+                button("Remove").on_press(MultiplayerTrackMessage::Remove).height(32),
+            ].align_x(Horizontal::Right),
+            column![
+                button("UP").on_press(MultiplayerTrackMessage::MoveTrackUp).height(16),
+                button("DOWN").on_press(MultiplayerTrackMessage::MoveTrackDown).height(16),
+            ].align_x(Horizontal::Right),
+        ]
+            .spacing(4)
+    )
+        .center_x(Fill)
+        .width(Fill)
+        .padding([2, 20]);
+
+    let mut content = column![
+        top_row,
+        audio_slider,
+    ];
+    if !track.cues.is_empty() {
+        content = content.push(container(cues_view(track)).padding([0, 40]));
+    }
+    if track.editing {
+        content = content.push(editor_view(track));
+    }
+
+    container(content).style(if currently_playing {container::rounded_box} else {container::dark})
+        .into()
+}
+
+pub fn playlist_view(room: &Room) -> Element<'_, Message> {
+    let playlist = &room.playlist;
+    let multiplayer_track_views: Vec<Element<MultiplayerPlaylistMessage>> = playlist.tracks.iter()
+        .enumerate()
+        .map(|index| track_view(index.1, playlist.current_track.is_some_and(|_| index.0 == playlist.current_track.unwrap()), room.is_layer_active(index.0)))
+        .enumerate()
+        .map(|(index, track)| {
+            track.map(move |message| MultiplayerPlaylistMessage::MultiplayerTrack(index, message))
+        })
+        .collect();
+    
+    let container: Element<'_, MultiplayerPlaylistMessage> = Container::new(
+        scrollable(
+            Column::with_children(multiplayer_track_views)
+        )
+    )
+        .height(Length::FillPortion(3))
+        .padding(10)
+        .center_x(Fill)
+        .into();
+    container.map(Message::MultiplayerPlaylist)
+}
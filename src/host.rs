@@ -1,4 +1,13 @@
-pub mod host;
+pub mod backend;
+pub mod effects;
+pub mod encoder;
+pub mod layer;
+pub mod loudness;
 pub mod playlist;
+pub mod resample;
+pub mod room;
 pub mod server;
+pub mod sound;
+pub mod source;
+pub mod tap;
 pub mod track;
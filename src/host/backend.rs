@@ -0,0 +1,259 @@
+use crate::settings::AudioOutput;
+use super::resample::OUTPUT_SAMPLE_RATE;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, StreamConfig};
+use kira::backend::cpal::Error;
+use kira::backend::{Backend, Renderer};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The kira backend the host mixes with. The mix is rendered by the system output device while local playback
+/// is on, and by a [`NullBackend`] thread otherwise or when there is no device, so nothing reaches the device
+/// in broadcast-only mode. Rooms are captured by their taps either way.
+pub struct OutputBackend {
+    device: Option<DeviceOutput>,
+    null: NullBackend,
+    // Whether the device renders the mix rather than the null thread. Only one of them ever does.
+    local_playback: Arc<AtomicBool>,
+}
+
+impl OutputBackend {
+    /// Whether there's no output device to play on.
+    pub fn is_null(&self) -> bool {
+        self.device.is_none()
+    }
+
+    /// Plays the mix on the output device, or stops using the device and keeps mixing for the taps alone.
+    pub fn set_local_playback(&mut self, local_playback: bool) {
+        let Some(device) = &self.device else {
+            return;
+        };
+        self.local_playback.store(local_playback, Ordering::Relaxed);
+        device.set_playing(local_playback);
+    }
+}
+
+impl Backend for OutputBackend {
+    type Settings = AudioOutput;
+    type Error = Error;
+
+    fn setup(settings: Self::Settings, internal_buffer_size: usize) -> Result<(Self, u32), Self::Error> {
+        let local_playback = Arc::new(AtomicBool::new(false));
+        if settings == AudioOutput::Device {
+            match DeviceOutput::open(local_playback.clone()) {
+                Ok(device) => {
+                    local_playback.store(true, Ordering::Relaxed);
+                    let sample_rate = device.config.sample_rate.0;
+                    let null = NullBackend::new(internal_buffer_size, sample_rate, local_playback.clone());
+                    return Ok((
+                        Self {
+                            device: Some(device),
+                            null,
+                            local_playback,
+                        },
+                        sample_rate,
+                    ));
+                }
+                Err(e) => println!("Audio output unavailable, mixing without a device: {}", e),
+            }
+        }
+        let null = NullBackend::new(internal_buffer_size, OUTPUT_SAMPLE_RATE, local_playback.clone());
+        Ok((
+            Self {
+                device: None,
+                null,
+                local_playback,
+            },
+            OUTPUT_SAMPLE_RATE,
+        ))
+    }
+
+    fn start(&mut self, renderer: Renderer) -> Result<(), Self::Error> {
+        let renderer = Arc::new(Mutex::new(renderer));
+        if let Some(device) = &mut self.device {
+            device.start(renderer.clone())?;
+        }
+        self.null.start(renderer);
+        Ok(())
+    }
+}
+
+/// Renders the mix in the output device's callback. cpal streams can't always be sent between threads,
+/// so the stream lives on a thread of its own that plays and pauses it when told to.
+struct DeviceOutput {
+    device: Option<Device>,
+    config: StreamConfig,
+    local_playback: Arc<AtomicBool>,
+    tx_playing: Option<mpsc::Sender<bool>>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl DeviceOutput {
+    fn open(local_playback: Arc<AtomicBool>) -> Result<Self, Error> {
+        let device = cpal::default_host().default_output_device().ok_or(Error::NoDefaultOutputDevice)?;
+        let config = device.default_output_config()?.config();
+        Ok(Self {
+            device: Some(device),
+            config,
+            local_playback,
+            tx_playing: None,
+            thread_handle: None,
+        })
+    }
+
+    fn start(&mut self, renderer: Arc<Mutex<Renderer>>) -> Result<(), Error> {
+        let device = self.device.take().expect("Cannot initialize the backend multiple times");
+        let config = self.config.clone();
+        let local_playback = self.local_playback.clone();
+        let (tx_playing, rx_playing) = mpsc::channel();
+        let (tx_started, rx_started) = mpsc::sync_channel(1);
+        let thread_handle = thread::spawn(move || {
+            let channels = config.channels;
+            let error_local_playback = local_playback.clone();
+            let stream = device.build_output_stream(
+                &config,
+                move |data: &mut [f32], _| {
+                    // The null thread renders while local playback is off
+                    let renderer = if local_playback.load(Ordering::Relaxed) { renderer.try_lock().ok() } else { None };
+                    match renderer {
+                        Some(mut renderer) => {
+                            renderer.on_start_processing();
+                            renderer.process(data, channels);
+                        }
+                        None => data.fill(0.0),
+                    }
+                },
+                move |e| {
+                    // Hand the mix to the null thread, so a lost device doesn't stop the streams too
+                    println!("Audio output error, mixing without the device: {}", e);
+                    error_local_playback.store(false, Ordering::Relaxed);
+                },
+                None,
+            );
+            let stream = match stream.map_err(Error::from).and_then(|stream| {
+                stream.play()?;
+                Ok(stream)
+            }) {
+                Ok(stream) => {
+                    let _ = tx_started.send(Ok(()));
+                    stream
+                }
+                Err(e) => {
+                    let _ = tx_started.send(Err(e));
+                    return;
+                }
+            };
+            // Runs until the backend is dropped along with the sender
+            while let Ok(playing) = rx_playing.recv() {
+                let result = if playing { stream.play().map_err(|e| e.to_string()) } else { stream.pause().map_err(|e| e.to_string()) };
+                if let Err(e) = result {
+                    println!("Error switching local playback: {}", e);
+                }
+            }
+        });
+        rx_started.recv().unwrap_or(Err(Error::NoDefaultOutputDevice))?;
+        self.tx_playing = Some(tx_playing);
+        self.thread_handle = Some(thread_handle);
+        Ok(())
+    }
+
+    fn set_playing(&self, playing: bool) {
+        if let Some(tx_playing) = &self.tx_playing {
+            let _ = tx_playing.send(playing);
+        }
+    }
+}
+
+impl Drop for DeviceOutput {
+    fn drop(&mut self) {
+        self.tx_playing = None;
+        if let Some(thread_handle) = self.thread_handle.take() {
+            let _ = thread_handle.join();
+        }
+    }
+}
+
+/// Drives the kira renderer from its own thread at real-time pace and throws the output away.
+/// On its own it runs at 48 kHz so the taps don't have to resample.
+pub struct NullBackend {
+    internal_buffer_size: usize,
+    sample_rate: u32,
+    // Paused while this is true, for when the output device renders instead
+    paused: Arc<AtomicBool>,
+    running: Arc<AtomicBool>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl NullBackend {
+    fn new(internal_buffer_size: usize, sample_rate: u32, paused: Arc<AtomicBool>) -> Self {
+        Self {
+            internal_buffer_size,
+            sample_rate,
+            paused,
+            running: Arc::new(AtomicBool::new(false)),
+            thread_handle: None,
+        }
+    }
+
+    fn start(&mut self, renderer: Arc<Mutex<Renderer>>) {
+        if self.thread_handle.is_some() {
+            panic!("Cannot initialize the backend multiple times")
+        }
+        let (internal_buffer_size, sample_rate) = (self.internal_buffer_size, self.sample_rate);
+        let (paused, running) = (self.paused.clone(), self.running.clone());
+        running.store(true, Ordering::Relaxed);
+        self.thread_handle = Some(thread::spawn(move || {
+            let mut buffer = vec![0.0; internal_buffer_size * 2];
+            // Counted from when rendering last resumed, so a pause isn't caught up on all at once
+            let mut started: Option<Instant> = None;
+            let mut frames_rendered = 0u64;
+            while running.load(Ordering::Relaxed) {
+                if paused.load(Ordering::Relaxed) {
+                    started = None;
+                    thread::sleep(Duration::from_millis(1));
+                    continue;
+                }
+                let started = *started.get_or_insert_with(|| {
+                    frames_rendered = 0;
+                    Instant::now()
+                });
+                let due = started.elapsed().as_secs_f64() * sample_rate as f64;
+                if (frames_rendered as f64) < due {
+                    let mut renderer = renderer.lock().unwrap();
+                    renderer.on_start_processing();
+                    renderer.process(&mut buffer, 2);
+                    frames_rendered += internal_buffer_size as u64;
+                } else {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }));
+    }
+}
+
+impl Backend for NullBackend {
+    type Settings = ();
+    type Error = Error;
+
+    fn setup(_settings: Self::Settings, internal_buffer_size: usize) -> Result<(Self, u32), Self::Error> {
+        let backend = Self::new(internal_buffer_size, OUTPUT_SAMPLE_RATE, Arc::new(AtomicBool::new(false)));
+        Ok((backend, OUTPUT_SAMPLE_RATE))
+    }
+
+    fn start(&mut self, renderer: Renderer) -> Result<(), Self::Error> {
+        NullBackend::start(self, Arc::new(Mutex::new(renderer)));
+        Ok(())
+    }
+}
+
+impl Drop for NullBackend {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread_handle) = self.thread_handle.take() {
+            let _ = thread_handle.join();
+        }
+    }
+}
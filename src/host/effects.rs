@@ -0,0 +1,164 @@
+use kira::effect::eq_filter::{EqFilterBuilder, EqFilterHandle, EqFilterKind};
+use kira::effect::filter::{FilterBuilder, FilterHandle, FilterMode};
+use kira::effect::reverb::{ReverbBuilder, ReverbHandle};
+use kira::track::TrackBuilder;
+use kira::{Decibels, Easing, Mix, StartTime, Tween};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Parameter changes glide over this long, so sweeping a slider doesn't crackle
+const SMOOTHING: Duration = Duration::from_millis(50);
+
+const LOW_SHELF_FREQUENCY: f64 = 200.0;
+const MID_FREQUENCY: f64 = 1000.0;
+const HIGH_SHELF_FREQUENCY: f64 = 5000.0;
+const EQ_Q: f64 = 0.7;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterKind {
+    #[default]
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+}
+
+impl FilterKind {
+    pub const ALL: [FilterKind; 4] = [Self::LowPass, Self::HighPass, Self::BandPass, Self::Notch];
+
+    fn mode(self) -> FilterMode {
+        match self {
+            Self::LowPass => FilterMode::LowPass,
+            Self::HighPass => FilterMode::HighPass,
+            Self::BandPass => FilterMode::BandPass,
+            Self::Notch => FilterMode::Notch,
+        }
+    }
+}
+
+impl std::fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::LowPass => "Low-pass",
+            Self::HighPass => "High-pass",
+            Self::BandPass => "Band-pass",
+            Self::Notch => "Notch",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    pub enabled: bool,
+    pub kind: FilterKind,
+    /// In hertz.
+    pub cutoff: f64,
+    /// From 0 to 1.
+    pub resonance: f64,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kind: FilterKind::default(),
+            cutoff: 1000.0,
+            resonance: 0.0,
+        }
+    }
+}
+
+/// A three band equalizer, gains in dB.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Equalizer {
+    pub enabled: bool,
+    pub low: f32,
+    pub mid: f32,
+    pub high: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Reverb {
+    pub enabled: bool,
+    /// How big the room sounds, from 0 to just under 1.
+    pub size: f64,
+    /// How quickly the high frequencies die away, from 0 to 1.
+    pub damping: f64,
+    /// How much of the reverberation is heard, from 0 to 1.
+    pub mix: f32,
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: 0.8,
+            damping: 0.3,
+            mix: 0.3,
+        }
+    }
+}
+
+/// The settings of an effect chain. Disabled effects stay in the chain, they just let the sound through.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Effects {
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub equalizer: Equalizer,
+    #[serde(default)]
+    pub reverb: Reverb,
+}
+
+/// Handles to the effects added to a kira track, in order: equalizer, filter, reverb.
+/// kira can't add effects to a track once it's built, so the whole chain is always there.
+pub struct EffectChain {
+    low: EqFilterHandle,
+    mid: EqFilterHandle,
+    high: EqFilterHandle,
+    filter: FilterHandle,
+    reverb: ReverbHandle,
+}
+
+impl EffectChain {
+    /// Adds the chain to a track being built, set up as `effects` says.
+    pub fn add_to(builder: &mut TrackBuilder, effects: &Effects) -> Self {
+        let mut chain = Self {
+            low: builder.add_effect(EqFilterBuilder::new(EqFilterKind::LowShelf, LOW_SHELF_FREQUENCY, Decibels::IDENTITY, EQ_Q)),
+            mid: builder.add_effect(EqFilterBuilder::new(EqFilterKind::Bell, MID_FREQUENCY, Decibels::IDENTITY, EQ_Q)),
+            high: builder.add_effect(EqFilterBuilder::new(EqFilterKind::HighShelf, HIGH_SHELF_FREQUENCY, Decibels::IDENTITY, EQ_Q)),
+            filter: builder.add_effect(FilterBuilder::new().mix(Mix::DRY)),
+            reverb: builder.add_effect(ReverbBuilder::new().mix(Mix::DRY)),
+        };
+        chain.apply(effects, Tween::default());
+        chain
+    }
+
+    /// Glides the effects to new settings.
+    pub fn set(&mut self, effects: &Effects) {
+        self.apply(effects, Tween {
+            start_time: StartTime::Immediate,
+            duration: SMOOTHING,
+            easing: Easing::Linear,
+        });
+    }
+
+    fn apply(&mut self, effects: &Effects, tween: Tween) {
+        let Equalizer { enabled, low, mid, high } = effects.equalizer;
+        let gain = |gain: f32| if enabled { Decibels(gain) } else { Decibels::IDENTITY };
+        self.low.set_gain(gain(low), tween);
+        self.mid.set_gain(gain(mid), tween);
+        self.high.set_gain(gain(high), tween);
+
+        let filter = effects.filter;
+        self.filter.set_mode(filter.kind.mode());
+        self.filter.set_cutoff(filter.cutoff, tween);
+        self.filter.set_resonance(filter.resonance, tween);
+        self.filter.set_mix(if filter.enabled { Mix::WET } else { Mix::DRY }, tween);
+
+        let reverb = effects.reverb;
+        self.reverb.set_feedback(reverb.size, tween);
+        self.reverb.set_damping(reverb.damping, tween);
+        self.reverb.set_mix(if reverb.enabled { Mix(reverb.mix) } else { Mix::DRY }, tween);
+    }
+}
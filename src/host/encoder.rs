@@ -0,0 +1,643 @@
+use super::source::AudioSource;
+
+use crate::lossless::{self, flac::FlacEncoder};
+use crate::meter::{LevelMeter, Levels};
+use crate::multistream::{self, MultistreamEncoder, MAX_PACKET_SIZE};
+use crate::protocol::{ChannelLayout, Codec, Frame, StreamParameters};
+use crate::settings::StreamQuality;
+use bytes::Bytes;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+const SOURCE_CHANNELS: usize = 2;
+const ENCODER_POLL_INTERVAL: Duration = Duration::from_millis(5);
+// Anything quieter than one 16-bit step counts as digital silence
+const SILENCE_THRESHOLD: f32 = 1.0 / 32768.0;
+// Half a second of silence at 48 kHz before the stream goes idle
+const SILENCE_HOLD_FRAMES: usize = 24000;
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+// Overlay audio older than 100 ms is dropped so a slow start doesn't turn into lasting delay
+const MAX_OVERLAY_SAMPLES: usize = 4800 * SOURCE_CHANNELS;
+// Consecutive restarts before the encoder gives up until it's told to retry
+const MAX_RESTARTS: u32 = 5;
+// Doubles with every consecutive restart
+const RESTART_BACKOFF: Duration = Duration::from_millis(500);
+// Running this long without an error forgives the earlier restarts
+const RECOVERED_AFTER: Duration = Duration::from_secs(10);
+
+enum Command {
+    Stop,
+    Restart,
+    SetLayout(ChannelLayout),
+    SetQuality(StreamQuality),
+    SetOverlay(Option<Box<dyn AudioSource + Send>>),
+}
+
+/// Where the packets of one codec go, and what clients need to decode them.
+#[derive(Clone)]
+pub struct Feed {
+    pub codec: Codec,
+    pub tx_capt: broadcast::Sender<Frame>,
+    pub parameters: Arc<Mutex<Option<StreamParameters>>>,
+}
+
+impl Feed {
+    pub fn new(codec: Codec) -> Self {
+        let (tx_capt, _) = broadcast::channel(16);
+        Self {
+            codec,
+            tx_capt,
+            parameters: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncoderStatus {
+    Starting,
+    Running,
+    /// Waiting to reopen the source or recreate the codecs after an error.
+    Restarting {
+        attempt: u32,
+        reason: String,
+    },
+    /// Gave up after too many restarts in a row, until `Encoder::restart`.
+    Failed(String),
+    Stopped,
+}
+
+impl fmt::Display for EncoderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Starting => f.write_str("Starting"),
+            Self::Running => f.write_str("Running"),
+            Self::Restarting { attempt, reason } => write!(f, "Restarting (attempt {} of {}): {}", attempt, MAX_RESTARTS, reason),
+            Self::Failed(reason) => write!(f, "Failed: {}", reason),
+            Self::Stopped => f.write_str("Stopped"),
+        }
+    }
+}
+
+/// Why a run of the encode loop ended early. Source failures, panics in the source included, reopen the source.
+/// The rest keep it.
+enum Failure {
+    Source(anyhow::Error),
+    Codec(anyhow::Error),
+    Panic(String),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Source(e) => write!(f, "Audio source error: {}", e),
+            Self::Codec(e) => write!(f, "Encoder error: {}", e),
+            Self::Panic(message) => write!(f, "Encoder panicked: {}", message),
+        }
+    }
+}
+
+/// Everything that outlives a restart of the encode loop.
+struct EncodeState {
+    layout: ChannelLayout,
+    quality: StreamQuality,
+    overlay: Option<Box<dyn AudioSource + Send>>,
+    feeds: Vec<Feed>,
+    meter: Arc<Mutex<LevelMeter>>,
+    status: Arc<Mutex<EncoderStatus>>,
+}
+
+impl EncodeState {
+    fn set_status(&self, status: EncoderStatus) {
+        *self.status.lock().unwrap() = status;
+    }
+
+    /// Keeps a command's setting for the next run. Returns whether to keep going.
+    fn store(&mut self, command: Command) -> bool {
+        match command {
+            Command::Stop => return false,
+            Command::Restart => {}
+            Command::SetLayout(layout) => self.layout = layout,
+            Command::SetQuality(quality) => self.quality = quality,
+            Command::SetOverlay(overlay) => self.overlay = overlay,
+        }
+        true
+    }
+}
+
+/// Pulls audio from an `AudioSource` on its own thread and encodes it once per feed,
+/// so Opus and lossless listeners can share a room. Feeds nobody listens to are skipped.
+/// Errors and panics restart the encoding, and only reopen the source if it was at fault.
+pub struct Encoder {
+    name: String,
+    thread_handle: Option<JoinHandle<()>>,
+    tx_command: Option<mpsc::Sender<Command>>,
+    meter: Arc<Mutex<LevelMeter>>,
+    status: Arc<Mutex<EncoderStatus>>,
+}
+
+impl Encoder {
+    /// `make_source` runs on the encoder thread, so sources tied to the thread that created them work too.
+    /// It's called again whenever the source has to be reopened.
+    pub fn spawn<F>(
+        name: &str,
+        feeds: Vec<Feed>,
+        layout: ChannelLayout,
+        quality: StreamQuality,
+        make_source: F,
+    ) -> Self
+    where
+        F: FnMut() -> anyhow::Result<Box<dyn AudioSource>> + Send + 'static,
+    {
+        let (tx_command, rx_command) = mpsc::channel();
+        let meter = Arc::new(Mutex::new(LevelMeter::new()));
+        let status = Arc::new(Mutex::new(EncoderStatus::Starting));
+
+        let state = EncodeState {
+            layout,
+            quality,
+            overlay: None,
+            feeds,
+            meter: meter.clone(),
+            status: status.clone(),
+        };
+        let thread_name = name.to_string();
+        let handle = thread::Builder::new()
+            .name(format!("Encoder {}", name))
+            .spawn(move || supervise(&thread_name, make_source, state, rx_command));
+        if let Err(e) = &handle {
+            *status.lock().unwrap() = EncoderStatus::Failed(format!("Couldn't start the encoder thread: {}", e));
+        }
+
+        Self {
+            name: name.to_string(),
+            thread_handle: handle.ok(),
+            tx_command: Some(tx_command),
+            meter,
+            status,
+        }
+    }
+
+    pub fn status(&self) -> EncoderStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Tries again after the encoder gave up.
+    pub fn restart(&self) {
+        if let Some(tx_command) = &self.tx_command {
+            let _ = tx_command.send(Command::Restart);
+        }
+    }
+
+    /// Levels of the audio encoded since the last call, before Opus touches it.
+    pub fn take_levels(&self) -> Levels {
+        self.meter.lock().unwrap().take()
+    }
+
+    /// Switches layouts between two packets. Listening clients are sent the new stream parameters first.
+    pub fn set_layout(&self, layout: ChannelLayout) {
+        if let Some(tx_command) = &self.tx_command {
+            let _ = tx_command.send(Command::SetLayout(layout));
+        }
+    }
+
+    /// Applies to the next packet. Listening clients are sent new stream parameters if the frame size changes.
+    pub fn set_quality(&self, quality: StreamQuality) {
+        if let Some(tx_command) = &self.tx_command {
+            let _ = tx_command.send(Command::SetQuality(quality));
+        }
+    }
+
+    /// Mixes a second source, like the host's microphone, on top of the main one.
+    /// An overlay that fails or panics is dropped without restarting the encoder.
+    pub fn set_overlay(&self, overlay: Option<Box<dyn AudioSource + Send>>) {
+        if let Some(tx_command) = &self.tx_command {
+            let _ = tx_command.send(Command::SetOverlay(overlay));
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(tx_command) = self.tx_command.take() {
+            let _ = tx_command.send(Command::Stop);
+        }
+        if let Some(thread_handle) = self.thread_handle.take() {
+            println!("Joining encoder thread of {}", self.name);
+            if thread_handle.join().is_err() {
+                println!("Encoder thread of {} panicked", self.name);
+            }
+        }
+    }
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn create_encoder(layout: ChannelLayout, quality: &StreamQuality) -> anyhow::Result<MultistreamEncoder> {
+    let mut opus_encoder = MultistreamEncoder::new(layout, quality.application)?;
+    apply_quality(&mut opus_encoder, quality)?;
+    Ok(opus_encoder)
+}
+
+fn apply_quality(opus_encoder: &mut MultistreamEncoder, quality: &StreamQuality) -> anyhow::Result<()> {
+    let channels = opus_encoder.layout().channels() as i32;
+    opus_encoder.set_bitrate(quality.bitrate as i32 * 1000 * channels)?;
+    opus_encoder.set_complexity(quality.complexity as i32)?;
+    opus_encoder.set_vbr(quality.vbr)?;
+    opus_encoder.set_bandwidth(quality.bandwidth)
+}
+
+fn publish(opus_encoder: &MultistreamEncoder, quality: &StreamQuality, feeds: &[Feed]) {
+    let frame_size = quality.frame_duration.frame_size();
+    for feed in feeds {
+        let stream_parameters = match feed.codec {
+            Codec::Opus => opus_encoder.parameters(frame_size),
+            codec => lossless::parameters(codec, opus_encoder.layout(), frame_size),
+        };
+        *feed.parameters.lock().unwrap() = Some(stream_parameters.clone());
+        // Sending only fails while nobody is listening
+        let _ = feed.tx_capt.send(Frame::Stream(stream_parameters));
+    }
+}
+
+/// Runs the encode loop until it's stopped, restarting it with a growing delay after each failure.
+fn supervise<F>(name: &str, mut make_source: F, mut state: EncodeState, rx_command: mpsc::Receiver<Command>)
+where
+    F: FnMut() -> anyhow::Result<Box<dyn AudioSource>>,
+{
+    let mut source: Option<Box<dyn AudioSource>> = None;
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let source = match source.as_mut() {
+                Some(source) => source,
+                None => source.insert(make_source().map_err(Failure::Source)?),
+            };
+            encode_loop(source.as_mut(), &mut state, &rx_command)
+        }))
+            .unwrap_or_else(|payload| Err(Failure::Panic(panic_message(payload))));
+
+        let failure = match result {
+            Ok(()) => {
+                println!("Canceling encoder loop");
+                state.set_status(EncoderStatus::Stopped);
+                return;
+            }
+            Err(failure) => failure,
+        };
+        if matches!(failure, Failure::Source(_)) {
+            source = None;
+        }
+        if started.elapsed() >= RECOVERED_AFTER {
+            attempt = 0;
+        }
+        attempt += 1;
+        let reason = failure.to_string();
+
+        if attempt > MAX_RESTARTS {
+            println!("Encoder {} failed: {}", name, reason);
+            state.set_status(EncoderStatus::Failed(reason));
+            // Settings still change while failed, so a retry picks them up
+            loop {
+                match rx_command.recv() {
+                    Ok(Command::Restart) => break,
+                    Ok(command) => {
+                        if !state.store(command) {
+                            state.set_status(EncoderStatus::Stopped);
+                            return;
+                        }
+                    }
+                    Err(_) => return,
+                }
+            }
+            attempt = 0;
+            state.set_status(EncoderStatus::Starting);
+            continue;
+        }
+
+        println!("Encoder {} restarting (attempt {}): {}", name, attempt, reason);
+        state.set_status(EncoderStatus::Restarting { attempt, reason });
+        let resume_at = Instant::now() + RESTART_BACKOFF * 2u32.pow(attempt - 1);
+        while let Some(wait) = resume_at.checked_duration_since(Instant::now()) {
+            match rx_command.recv_timeout(wait) {
+                Ok(command) => {
+                    if !state.store(command) {
+                        state.set_status(EncoderStatus::Stopped);
+                        return;
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload.downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("unknown cause"))
+}
+
+/// Encodes until `Command::Stop`, which is the only way it returns `Ok`.
+fn encode_loop(
+    source: &mut dyn AudioSource,
+    state: &mut EncodeState,
+    rx_command: &mpsc::Receiver<Command>,
+) -> Result<(), Failure> {
+    let mut sample_queue: Vec<f32> = Vec::new();
+
+    let mut opus_encoder = create_encoder(state.layout, &state.quality).map_err(Failure::Codec)?;
+    let mut flac_encoder = FlacEncoder::new();
+    publish(&opus_encoder, &state.quality, &state.feeds);
+    state.set_status(EncoderStatus::Running);
+
+    let mut overlay_queue: Vec<f32> = Vec::new();
+    let mut silent_frames = 0;
+    let mut last_keepalive: Option<Instant> = None;
+
+    loop {
+        while let Ok(command) = rx_command.try_recv() {
+            match command {
+                Command::Stop => return Ok(()),
+                Command::Restart => {}
+                Command::SetLayout(layout) => {
+                    if layout != state.layout {
+                        state.layout = layout;
+                        opus_encoder = create_encoder(layout, &state.quality).map_err(Failure::Codec)?;
+                        publish(&opus_encoder, &state.quality, &state.feeds);
+                    }
+                }
+                Command::SetOverlay(overlay) => {
+                    state.overlay = overlay;
+                    overlay_queue.clear();
+                }
+                Command::SetQuality(quality) => {
+                    // The application can't be switched to or from low delay once encoding started
+                    if quality.application != state.quality.application {
+                        opus_encoder = create_encoder(state.layout, &quality).map_err(Failure::Codec)?;
+                    } else if let Err(e) = apply_quality(&mut opus_encoder, &quality) {
+                        println!("Error applying stream quality: {}", e);
+                    }
+                    let frame_size_changed = quality.frame_duration != state.quality.frame_duration;
+                    state.quality = quality;
+                    if frame_size_changed {
+                        publish(&opus_encoder, &state.quality, &state.feeds);
+                    }
+                }
+            }
+        }
+        // Whatever state a panicking source was left in isn't worth trusting, so it's reopened like after an error
+        panic::catch_unwind(AssertUnwindSafe(|| source.read(&mut sample_queue)))
+            .unwrap_or_else(|payload| Err(anyhow::anyhow!("Panicked: {}", panic_message(payload))))
+            .map_err(Failure::Source)?;
+        if let Some(overlay) = state.overlay.as_mut() {
+            // A broken overlay only costs the overlay, the music keeps going, even if it panicked
+            let result = panic::catch_unwind(AssertUnwindSafe(|| overlay.read(&mut overlay_queue)))
+                .unwrap_or_else(|payload| Err(anyhow::anyhow!("Panicked: {}", panic_message(payload))));
+            if let Err(e) = result {
+                println!("Dropping overlay after error: {}", e);
+                state.overlay = None;
+                overlay_queue.clear();
+            }
+            let excess = overlay_queue.len().saturating_sub(MAX_OVERLAY_SAMPLES);
+            overlay_queue.drain(..excess - excess % SOURCE_CHANNELS);
+        }
+        let frame_size = state.quality.frame_duration.frame_size();
+        while sample_queue.len() >= frame_size * SOURCE_CHANNELS {
+            let mut stereo: Vec<f32> = sample_queue.drain(..frame_size * SOURCE_CHANNELS).collect();
+            let mixed = stereo.len().min(overlay_queue.len());
+            for (sample, overlay_sample) in stereo.iter_mut().zip(overlay_queue.drain(..mixed)) {
+                *sample += overlay_sample;
+            }
+            state.meter.lock().unwrap().process(&stereo, SOURCE_CHANNELS);
+            if stereo.iter().all(|sample| sample.abs() <= SILENCE_THRESHOLD) {
+                silent_frames += frame_size;
+            } else {
+                silent_frames = 0;
+            }
+            // Idle: keep the connections alive instead of encoding silence, until the first audible frame
+            if silent_frames >= SILENCE_HOLD_FRAMES {
+                if last_keepalive.is_none_or(|sent| sent.elapsed() >= KEEPALIVE_INTERVAL) {
+                    for feed in state.feeds.iter() {
+                        let _ = feed.tx_capt.send(Frame::Keepalive);
+                    }
+                    last_keepalive = Some(Instant::now());
+                }
+                continue;
+            }
+            last_keepalive = None;
+            let frame = multistream::from_stereo(state.layout, &stereo);
+            for feed in state.feeds.iter() {
+                if feed.tx_capt.receiver_count() == 0 {
+                    continue;
+                }
+                let packet = match feed.codec {
+                    Codec::Opus => opus_encoder
+                        .encode_vec_float(frame.as_slice(), MAX_PACKET_SIZE)
+                        .map_err(Failure::Codec)?,
+                    Codec::Pcm => lossless::encode_pcm(&frame),
+                    Codec::Flac => flac_encoder.encode(&frame, state.layout.channels()),
+                };
+                let _ = feed.tx_capt.send(Frame::Audio(Bytes::from(packet)));
+            }
+        }
+        thread::sleep(ENCODER_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::source::file::FileSource;
+    use crate::host::source::silence::SilenceSource;
+    use crate::host::source::sine::SineSource;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    struct PanicOnce(Arc<AtomicBool>);
+
+    impl AudioSource for PanicOnce {
+        fn read(&mut self, _output: &mut Vec<f32>) -> anyhow::Result<()> {
+            if !self.0.swap(true, Ordering::Relaxed) {
+                panic!("source broke");
+            }
+            Ok(())
+        }
+    }
+
+    /// Panics on every read, and counts them.
+    struct PanickingOverlay {
+        reads: Arc<AtomicUsize>,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl AudioSource for PanickingOverlay {
+        fn read(&mut self, _output: &mut Vec<f32>) -> anyhow::Result<()> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            panic!("overlay broke");
+        }
+    }
+
+    impl Drop for PanickingOverlay {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn wait_for(encoder: &Encoder, matches: impl Fn(&EncoderStatus) -> bool) -> EncoderStatus {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let status = encoder.status();
+            if matches(&status) || Instant::now() > deadline {
+                return status;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn panicking_overlay_is_dropped_without_a_restart() {
+        // The source can only be made once, so a restart would end up failed
+        let mut made = false;
+        let mut encoder = Encoder::spawn("test", Vec::new(), ChannelLayout::Stereo, StreamQuality::default(), move || {
+            if made {
+                anyhow::bail!("The source can't be reopened");
+            }
+            made = true;
+            Ok(Box::new(SilenceSource::new()) as Box<dyn AudioSource>)
+        });
+        assert_eq!(wait_for(&encoder, |status| *status == EncoderStatus::Running), EncoderStatus::Running);
+
+        let reads = Arc::new(AtomicUsize::new(0));
+        let dropped = Arc::new(AtomicBool::new(false));
+        encoder.set_overlay(Some(Box::new(PanickingOverlay {
+            reads: reads.clone(),
+            dropped: dropped.clone(),
+        })));
+        let watch_until = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < watch_until {
+            assert_eq!(encoder.status(), EncoderStatus::Running);
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
+        assert!(dropped.load(Ordering::Relaxed));
+
+        encoder.stop();
+        assert_eq!(encoder.status(), EncoderStatus::Stopped);
+    }
+
+    #[test]
+    fn panicking_source_is_reopened() {
+        let panicked = Arc::new(AtomicBool::new(false));
+        let mut opened = 0;
+        let opened_panicked = panicked.clone();
+        let mut encoder = Encoder::spawn("test", Vec::new(), ChannelLayout::Stereo, StreamQuality::default(), move || {
+            opened += 1;
+            if opened == 1 {
+                Ok(Box::new(PanicOnce(opened_panicked.clone())) as Box<dyn AudioSource>)
+            } else {
+                Ok(Box::new(SilenceSource::new()) as Box<dyn AudioSource>)
+            }
+        });
+        let status = wait_for(&encoder, |status| matches!(status, EncoderStatus::Restarting { .. }));
+        assert!(matches!(&status, EncoderStatus::Restarting { reason, .. } if reason.starts_with("Audio source error")), "{}", status);
+        assert_eq!(wait_for(&encoder, |status| *status == EncoderStatus::Running), EncoderStatus::Running);
+        encoder.stop();
+    }
+
+    /// The next audio packet sent to `rx`, skipping stream parameters and keepalives.
+    fn next_packet(rx: &mut broadcast::Receiver<Frame>) -> Bytes {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            match rx.try_recv() {
+                Ok(Frame::Audio(packet)) => return packet,
+                Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                Err(_) => thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        panic!("No audio packet within 5 seconds");
+    }
+
+    fn peak(packet: &[u8]) -> f32 {
+        let mut samples = Vec::new();
+        lossless::decode_pcm(packet, &mut samples).unwrap();
+        samples.iter().fold(0.0, |peak, sample| sample.abs().max(peak))
+    }
+
+    /// One second of a 440 Hz tone at half scale, as 44.1 kHz 16-bit stereo WAV.
+    fn write_wav(path: &std::path::Path) {
+        let sample_rate = 44100u32;
+        let data = (0..sample_rate)
+            .flat_map(|frame| {
+                let sample = ((std::f64::consts::TAU * 440.0 * frame as f64 / sample_rate as f64).sin() * 16384.0) as i16;
+                [sample, sample]
+            })
+            .flat_map(i16::to_le_bytes)
+            .collect::<Vec<u8>>();
+        let mut wav = Vec::new();
+        wav.extend(b"RIFF");
+        wav.extend((36 + data.len() as u32).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(2u16.to_le_bytes());
+        wav.extend(sample_rate.to_le_bytes());
+        wav.extend((sample_rate * 4).to_le_bytes());
+        wav.extend(4u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend((data.len() as u32).to_le_bytes());
+        wav.extend(data);
+        std::fs::write(path, wav).unwrap();
+    }
+
+    #[test]
+    fn sine_source_is_encoded_for_every_feed() {
+        let feeds = vec![Feed::new(Codec::Opus), Feed::new(Codec::Pcm)];
+        let mut opus_rx = feeds[0].tx_capt.subscribe();
+        let mut pcm_rx = feeds[1].tx_capt.subscribe();
+        let quality = StreamQuality::default();
+        let mut encoder = Encoder::spawn("test", feeds.clone(), ChannelLayout::Stereo, quality, || {
+            Ok(Box::new(SineSource::new(1000.0, 0.5)) as Box<dyn AudioSource>)
+        });
+
+        assert!(!next_packet(&mut opus_rx).is_empty());
+        let packet = next_packet(&mut pcm_rx);
+        // 16-bit stereo samples
+        assert_eq!(packet.len(), quality.frame_duration.frame_size() * 2 * 2);
+        assert!((peak(&packet) - 0.5).abs() < 0.01, "{}", peak(&packet));
+        let parameters = feeds[1].parameters.lock().unwrap().clone().unwrap();
+        assert_eq!(parameters.codec, Codec::Pcm);
+        assert_eq!(parameters.frame_size, quality.frame_duration.frame_size());
+        encoder.stop();
+    }
+
+    #[test]
+    fn file_source_is_encoded() {
+        let path = std::env::temp_dir().join(format!("multiplayer-file-source-{}.wav", std::process::id()));
+        write_wav(&path);
+        let source_path = path.to_str().unwrap().to_string();
+        let feeds = vec![Feed::new(Codec::Pcm)];
+        let mut rx = feeds[0].tx_capt.subscribe();
+        let mut encoder = Encoder::spawn("test", feeds, ChannelLayout::Stereo, StreamQuality::default(), move || {
+            Ok(Box::new(FileSource::new(&source_path, true)?) as Box<dyn AudioSource>)
+        });
+
+        // Resampled from 44.1 kHz, so the peak comes out close to the file's rather than exactly it
+        let packet = next_packet(&mut rx);
+        let packet = if peak(&packet) < 0.1 { next_packet(&mut rx) } else { packet };
+        assert!((peak(&packet) - 0.5).abs() < 0.02, "{}", peak(&packet));
+        assert_eq!(encoder.status(), EncoderStatus::Running);
+        encoder.stop();
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+use kira::sound::{PlaybackState, Region};
+use kira::track::{TrackBuilder, TrackHandle};
+use kira::{Decibels, Tween};
+use super::effects::{EffectChain, Effects};
+use super::sound::{PlaySettings, Sound};
+use super::track::MultiplayerTrack;
+
+/// One playlist track playing on its own kira sub-track, so it fades and sets its volume independently of the
+/// other layers. The volume is tweened on the sub-track itself, which keeps layers from using up the manager's
+/// modulators.
+pub struct Layer {
+    index: usize,
+    main: bool,
+    fading_out: bool,
+    track_handle: TrackHandle,
+    sound: Sound,
+    effects: EffectChain,
+}
+
+/// Maps a 0 to 1 slider volume the way it always has been, linearly onto the decibel range.
+pub(super) fn decibels(volume: f64) -> Decibels {
+    let range = Decibels::IDENTITY.0 - Decibels::SILENCE.0;
+    Decibels(Decibels::SILENCE.0 + range * volume.clamp(0.0, 1.0) as f32)
+}
+
+impl Layer {
+    /// Starts `track` silent on a new sub-track of `parent`, through the track's own effects, and fades it in to its
+    /// volume. None if kira has no sub-tracks left or the sound couldn't be played.
+    pub fn start(parent: &mut TrackHandle, index: usize, main: bool, track: &MultiplayerTrack, settings: PlaySettings, fade_in: Tween) -> Option<Self> {
+        let mut builder = TrackBuilder::new().volume(Decibels::SILENCE);
+        let effects = EffectChain::add_to(&mut builder, &track.effects);
+        let mut track_handle = match parent.add_sub_track(builder) {
+            Ok(track_handle) => track_handle,
+            Err(e) => {
+                println!("Couldn't add a track for {}: {}", track.path, e);
+                return None;
+            }
+        };
+        let sound = track.data.play(&mut track_handle, settings)?;
+        track_handle.set_volume(decibels(track.volume), fade_in);
+        Some(Self {
+            index,
+            main,
+            fading_out: false,
+            track_handle,
+            sound,
+            effects,
+        })
+    }
+
+    /// The playlist track this layer plays.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Whether this is the playlist's current track rather than a layer started on top of it.
+    pub fn is_main(&self) -> bool {
+        self.main
+    }
+
+    /// Playing, or waiting to, as opposed to fading out or stopped.
+    pub fn is_active(&self) -> bool {
+        !self.fading_out && self.sound.state() != PlaybackState::Stopped
+    }
+
+    pub fn position(&self) -> f64 {
+        self.sound.position()
+    }
+
+    pub fn state(&self) -> PlaybackState {
+        self.sound.state()
+    }
+
+    pub(super) fn set_main(&mut self, main: bool) {
+        self.main = main;
+    }
+
+    pub(super) fn set_index(&mut self, index: usize) {
+        self.index = index;
+    }
+
+    pub(super) fn set_volume(&mut self, volume: f64, tween: Tween) {
+        self.track_handle.set_volume(decibels(volume), tween);
+    }
+
+    /// Sets the loudness normalization gain in dB, on the sound so it stays apart from the user volume.
+    pub(super) fn set_gain(&mut self, gain: f64, tween: Tween) {
+        self.sound.set_volume(Decibels(gain as f32), tween);
+    }
+
+    pub(super) fn set_effects(&mut self, effects: &Effects) {
+        self.effects.set(effects);
+    }
+
+    pub(super) fn set_loop_region(&mut self, loop_region: Option<Region>) {
+        self.sound.set_loop_region(loop_region);
+    }
+
+    pub(super) fn seek_to(&mut self, position: f64) {
+        self.sound.seek_to(position);
+    }
+
+    pub(super) fn pause(&mut self, tween: Tween) {
+        self.sound.pause(tween);
+    }
+
+    pub(super) fn resume(&mut self, tween: Tween) {
+        if self.sound.state() == PlaybackState::Paused {
+            self.sound.resume(tween);
+        }
+    }
+
+    /// Stops the sound with `fade_out`. The layer is kept until the sound has stopped.
+    pub(super) fn fade_out(&mut self, fade_out: Tween) {
+        self.sound.stop(fade_out);
+        // The volume falls slower than the sound itself fades, so the stop does the fading
+        let volume_fade_out = Tween {
+            duration: fade_out.duration * 2,
+            ..fade_out
+        };
+        self.track_handle.set_volume(Decibels::SILENCE, volume_fade_out);
+        self.fading_out = true;
+        self.main = false;
+    }
+}
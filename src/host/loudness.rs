@@ -0,0 +1,273 @@
+use kira::Frame;
+use std::f64::consts::PI;
+
+// Gating as in ITU-R BS.1770-4: 400 ms blocks overlapping by 75 %
+const BLOCK_SECONDS: f64 = 0.4;
+const STEP_SECONDS: f64 = 0.1;
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Most a quiet track gets boosted by, in dB, so normalizing doesn't bring up its noise floor too far.
+/// Clipping is prevented by the track's peak, see `normalization_gain`.
+pub const MAX_BOOST: f64 = 12.0;
+
+/// A biquad filter in direct form I.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[1] * self.y[0] - self.a[2] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// The two stage K-weighting filter, a high shelf for the head's effect and a high pass, for any sample rate.
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let rate = sample_rate as f64;
+
+        let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        };
+
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        };
+
+        Self {
+            shelf,
+            high_pass,
+        }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+fn loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+/// What a `LoudnessMeter` found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    /// Integrated loudness in LUFS.
+    pub loudness: f64,
+    /// The loudest sample in dBFS. A sample peak, so peaks between samples can still go a little over it.
+    pub peak: f64,
+}
+
+/// Measures the integrated loudness of stereo audio fed to it a frame at a time, so long files don't have to be
+/// decoded into memory first.
+pub struct LoudnessMeter {
+    left: KWeighting,
+    right: KWeighting,
+    step: usize,
+    // Sum of the weighted squares of both channels over the step in progress
+    sum: f64,
+    frames: usize,
+    // Sums over each finished 100 ms step, so blocks can share them
+    steps: Vec<f64>,
+    peak: f32,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            left: KWeighting::new(sample_rate),
+            right: KWeighting::new(sample_rate),
+            step: (sample_rate as f64 * STEP_SECONDS) as usize,
+            sum: 0.0,
+            frames: 0,
+            steps: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, frame: Frame) {
+        self.peak = self.peak.max(frame.left.abs()).max(frame.right.abs());
+        let (left, right) = (self.left.process(frame.left as f64), self.right.process(frame.right as f64));
+        self.sum += left * left + right * right;
+        self.frames += 1;
+        if self.frames == self.step {
+            self.steps.push(self.sum);
+            self.sum = 0.0;
+            self.frames = 0;
+        }
+    }
+
+    /// The integrated loudness in LUFS, as EBU R128 measures it, and the sample peak. None if what was pushed is
+    /// too short or too quiet to measure.
+    pub fn finish(self) -> Option<Measurement> {
+        let steps_per_block = (BLOCK_SECONDS / STEP_SECONDS).round() as usize;
+        if self.step == 0 {
+            return None;
+        }
+
+        let block_length = (self.step * steps_per_block) as f64;
+        let blocks = self.steps.windows(steps_per_block)
+            .map(|window| window.iter().sum::<f64>() / block_length)
+            .filter(|mean_square| loudness(*mean_square) > ABSOLUTE_GATE)
+            .collect::<Vec<f64>>();
+        if blocks.is_empty() {
+            return None;
+        }
+
+        let relative_gate = loudness(blocks.iter().sum::<f64>() / blocks.len() as f64) + RELATIVE_GATE;
+        let gated = blocks.into_iter()
+            .filter(|mean_square| loudness(*mean_square) > relative_gate)
+            .collect::<Vec<f64>>();
+        Some(Measurement {
+            loudness: loudness(gated.iter().sum::<f64>() / gated.len() as f64),
+            peak: 20.0 * (self.peak as f64).log10(),
+        })
+    }
+}
+
+/// The integrated loudness of stereo audio in LUFS, as EBU R128 measures it. None if it's too short or too quiet
+/// to measure.
+pub fn integrated_loudness(frames: &[Frame], sample_rate: u32) -> Option<f64> {
+    measure(frames, sample_rate).map(|measurement| measurement.loudness)
+}
+
+/// Measures stereo audio that's all in memory.
+pub fn measure(frames: &[Frame], sample_rate: u32) -> Option<Measurement> {
+    let mut meter = LoudnessMeter::new(sample_rate);
+    for frame in frames {
+        meter.push(*frame);
+    }
+    meter.finish()
+}
+
+/// The gain in dB that brings a track at `loudness` to `target`, both in LUFS. Boosts only go as far as the
+/// headroom above the track's `peak` in dBFS, so normalizing doesn't clip it.
+pub fn normalization_gain(loudness: f64, peak: f64, target: f64) -> f64 {
+    (target - loudness).min(MAX_BOOST).min((-peak).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn sine(frequency: f64, amplitude: f32, seconds: f64) -> Vec<Frame> {
+        let frames = (SAMPLE_RATE as f64 * seconds) as usize;
+        (0..frames)
+            .map(|frame| {
+                let sample = (TAU * frequency * frame as f64 / SAMPLE_RATE as f64).sin() as f32 * amplitude;
+                Frame::new(sample, sample)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn full_scale_sine_in_one_channel_reads_minus_three() {
+        // The reference from BS.1770: a 0 dBFS 997 Hz sine in one channel is -3.01 LUFS
+        let frames = sine(997.0, 1.0, 5.0)
+            .into_iter()
+            .map(|frame| Frame::new(frame.left, 0.0))
+            .collect::<Vec<Frame>>();
+        let loudness = integrated_loudness(&frames, SAMPLE_RATE).unwrap();
+        assert!((loudness + 3.01).abs() < 0.05, "{loudness}");
+    }
+
+    #[test]
+    fn loudness_follows_level() {
+        let loudness = integrated_loudness(&sine(997.0, 1.0, 5.0), SAMPLE_RATE).unwrap();
+        assert!(loudness.abs() < 0.05, "{loudness}");
+        let loudness = integrated_loudness(&sine(997.0, 0.1, 5.0), SAMPLE_RATE).unwrap();
+        assert!((loudness + 20.0).abs() < 0.05, "{loudness}");
+    }
+
+    #[test]
+    fn other_sample_rates_measure_the_same() {
+        let frames = (0..44100 * 5)
+            .map(|frame| {
+                let sample = (TAU * 997.0 * frame as f64 / 44100.0).sin() as f32 * 0.1;
+                Frame::new(sample, sample)
+            })
+            .collect::<Vec<Frame>>();
+        let loudness = integrated_loudness(&frames, 44100).unwrap();
+        assert!((loudness + 20.0).abs() < 0.05, "{loudness}");
+    }
+
+    #[test]
+    fn silence_is_gated_out() {
+        // Only the few blocks straddling the end of the sine get through the gates
+        let mut frames = sine(997.0, 0.1, 5.0);
+        frames.extend(std::iter::repeat_n(Frame::ZERO, SAMPLE_RATE as usize * 20));
+        let loudness = integrated_loudness(&frames, SAMPLE_RATE).unwrap();
+        assert!((loudness + 20.0).abs() < 0.2, "{loudness}");
+        assert_eq!(integrated_loudness(&vec![Frame::ZERO; SAMPLE_RATE as usize], SAMPLE_RATE), None);
+    }
+
+    #[test]
+    fn too_short_to_measure() {
+        assert_eq!(integrated_loudness(&sine(997.0, 0.5, 0.2), SAMPLE_RATE), None);
+    }
+
+    #[test]
+    fn meter_measures_the_same_pushed_frame_by_frame() {
+        let frames = sine(997.0, 0.1, 5.0);
+        let mut meter = LoudnessMeter::new(SAMPLE_RATE);
+        for frame in &frames[..12345] {
+            meter.push(*frame);
+        }
+        for frame in &frames[12345..] {
+            meter.push(*frame);
+        }
+        assert_eq!(meter.finish(), measure(&frames, SAMPLE_RATE));
+    }
+
+    #[test]
+    fn peak_is_the_loudest_sample() {
+        let mut frames = sine(997.0, 0.1, 5.0);
+        frames[1000] = Frame::new(0.0, -0.5);
+        let peak = measure(&frames, SAMPLE_RATE).unwrap().peak;
+        assert!((peak + 6.02).abs() < 0.01, "{peak}");
+    }
+
+    #[test]
+    fn quiet_tracks_are_boosted_only_so_far() {
+        assert_eq!(normalization_gain(-13.0, -1.0, -23.0), -10.0);
+        assert_eq!(normalization_gain(-40.0, -30.0, -23.0), MAX_BOOST);
+    }
+
+    #[test]
+    fn boosts_stop_at_the_peak() {
+        assert_eq!(normalization_gain(-33.0, -4.0, -23.0), 4.0);
+        assert_eq!(normalization_gain(-33.0, 0.5, -23.0), 0.0);
+        // Cutting a track that's already too loud isn't limited
+        assert_eq!(normalization_gain(-13.0, 0.5, -23.0), -10.0);
+    }
+}
@@ -1,12 +1,134 @@
+use super::effects::Effects;
+use kira::{Easing, StartTime, Tween};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub name: String,
+    /// Seconds from the start of the file.
+    pub position: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl FadeCurve {
+    pub const ALL: [FadeCurve; 4] = [Self::Linear, Self::EaseIn, Self::EaseOut, Self::EaseInOut];
+
+    pub fn easing(self) -> Easing {
+        match self {
+            Self::Linear => Easing::Linear,
+            Self::EaseIn => Easing::InPowi(3),
+            Self::EaseOut => Easing::OutPowi(3),
+            Self::EaseInOut => Easing::InOutPowi(3),
+        }
+    }
+}
+
+impl std::fmt::Display for FadeCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Linear => "Linear",
+            Self::EaseIn => "Ease in",
+            Self::EaseOut => "Ease out",
+            Self::EaseInOut => "Ease in and out",
+        })
+    }
+}
+
+/// A track's own fade, used instead of the host's.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Fade {
+    /// In milliseconds.
+    pub duration: u64,
+    pub curve: FadeCurve,
+}
+
+impl Default for Fade {
+    fn default() -> Self {
+        Self {
+            duration: 2000,
+            curve: FadeCurve::default(),
+        }
+    }
+}
+
+impl Fade {
+    pub fn tween(self, start_time: StartTime) -> Tween {
+        Tween {
+            start_time,
+            duration: Duration::from_millis(self.duration),
+            easing: self.curve.easing(),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Track {
     pub path: String,
     pub volume: f64,
+    /// Where looping starts over, in seconds. Whatever comes before only plays once.
+    #[serde(default)]
+    pub loop_start: Option<f64>,
+    /// Where looping jumps back, in seconds. The end of the file if not set.
+    #[serde(default)]
+    pub loop_end: Option<f64>,
+    #[serde(default)]
+    pub cues: Vec<Cue>,
+    /// Where the track starts playing, in seconds.
+    #[serde(default)]
+    pub start_offset: f64,
+    /// Where the track stops playing, in seconds. The end of the file if not set.
+    #[serde(default)]
+    pub trim_end: Option<f64>,
+    #[serde(default)]
+    pub fade_in: Option<Fade>,
+    #[serde(default)]
+    pub fade_out: Option<Fade>,
+    /// Plays once over the music when triggered, instead of becoming the current track.
+    #[serde(default)]
+    pub sfx: bool,
+    #[serde(default)]
+    pub effects: Effects,
+    /// Integrated loudness in LUFS, measured when the file was imported.
+    #[serde(default)]
+    pub loudness: Option<f64>,
+    /// Sample peak in dBFS, measured along with the loudness.
+    #[serde(default)]
+    pub peak: Option<f64>,
+}
+
+/// A track as it was playing when its scene was saved.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneLayer {
+    /// Tracks are found by path, so a scene survives the playlist being reordered.
+    pub path: String,
+    pub position: f64,
+    pub volume: f64,
+    /// Whether it was the current track rather than a layer on top of it.
+    pub main: bool,
+}
+
+/// A named mix of tracks to go back to, like "Tavern" or "Boss fight".
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scene {
+    pub name: String,
+    pub layers: Vec<SceneLayer>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Playlist {
     pub tracks: Vec<Track>,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub scenes: Vec<Scene>,
+    /// The room's effect chain, which everything played through it goes through.
+    #[serde(default)]
+    pub master_effects: Effects,
+}
@@ -0,0 +1,135 @@
+pub const OUTPUT_SAMPLE_RATE: u32 = 48000;
+
+/// Cubic Hermite interpolation between stereo frames, to line a source rate up with what Opus accepts.
+/// There is no low-pass filter, so content above 24 kHz in a 96 kHz source folds back.
+pub struct Resampler {
+    position: f64,
+    // Interleaved frames not consumed yet, starting one frame before `position`
+    history: Vec<f32>,
+}
+
+impl Default for Resampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resampler {
+    pub fn new() -> Self {
+        Self {
+            position: 1.0,
+            history: vec![0.0; 2],
+        }
+    }
+
+    /// Converts interleaved stereo `input` at `input_rate` to 48 kHz and appends it to `output`.
+    /// Keeps the last few frames between calls, so a stream can be fed in chunks of any size.
+    pub fn process(&mut self, input_rate: u32, input: &[f32], output: &mut Vec<f32>) {
+        if input_rate == OUTPUT_SAMPLE_RATE {
+            output.extend_from_slice(input);
+            return;
+        }
+        let step = input_rate as f64 / OUTPUT_SAMPLE_RATE as f64;
+        self.history.extend_from_slice(&input[..input.len() - input.len() % 2]);
+        let frames = self.history.len() / 2;
+        loop {
+            let index = self.position as usize;
+            if index + 2 >= frames {
+                break;
+            }
+            let t = (self.position - index as f64) as f32;
+            for channel in 0..2 {
+                let sample = |frame: usize| self.history[frame * 2 + channel];
+                output.push(hermite(sample(index - 1), sample(index), sample(index + 1), sample(index + 2), t));
+            }
+            self.position += step;
+        }
+        let consumed = (self.position as usize - 1).min(frames);
+        self.history.drain(..consumed * 2);
+        self.position -= consumed as f64;
+    }
+}
+
+fn hermite(previous: f32, from: f32, to: f32, next: f32, t: f32) -> f32 {
+    let c1 = 0.5 * (to - previous);
+    let c2 = previous - 2.5 * from + 2.0 * to - 0.5 * next;
+    let c3 = 0.5 * (next - previous) + 1.5 * (from - to);
+    ((c3 * t + c2) * t + c1) * t + from
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    const FREQUENCY: f64 = 1000.0;
+
+    fn sine(sample_rate: u32, seconds: f64) -> Vec<f32> {
+        let frames = (sample_rate as f64 * seconds) as usize;
+        (0..frames)
+            .flat_map(|frame| {
+                let sample = (TAU * FREQUENCY * frame as f64 / sample_rate as f64).sin() as f32 * 0.5;
+                [sample, -sample]
+            })
+            .collect()
+    }
+
+    fn resample_in_chunks(input_rate: u32, input: &[f32], chunk_frames: usize) -> Vec<f32> {
+        let mut resampler = Resampler::new();
+        let mut output = Vec::new();
+        for chunk in input.chunks(chunk_frames * 2) {
+            resampler.process(input_rate, chunk, &mut output);
+        }
+        output
+    }
+
+    fn max_error(output: &[f32]) -> f32 {
+        output
+            .chunks(2)
+            .enumerate()
+            .map(|(frame, samples)| {
+                let expected = (TAU * FREQUENCY * frame as f64 / OUTPUT_SAMPLE_RATE as f64).sin() as f32 * 0.5;
+                (samples[0] - expected).abs().max((samples[1] + expected).abs())
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn passes_48k_through() {
+        let input = sine(48000, 0.1);
+        let output = resample_in_chunks(48000, &input, 480);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn converts_44_1k() {
+        let output = resample_in_chunks(44100, &sine(44100, 1.0), 441);
+        assert!(output.len().abs_diff(96000) <= 8, "got {} samples", output.len());
+        assert!(max_error(&output) < 1e-3, "max error {}", max_error(&output));
+    }
+
+    #[test]
+    fn converts_96k() {
+        let output = resample_in_chunks(96000, &sine(96000, 1.0), 960);
+        assert!(output.len().abs_diff(96000) <= 8, "got {} samples", output.len());
+        assert!(max_error(&output) < 1e-4, "max error {}", max_error(&output));
+    }
+
+    #[test]
+    fn converts_22_05k() {
+        let output = resample_in_chunks(22050, &sine(22050, 1.0), 100);
+        assert!(output.len().abs_diff(96000) <= 8, "got {} samples", output.len());
+        assert!(max_error(&output) < 2e-2, "max error {}", max_error(&output));
+    }
+
+    #[test]
+    fn chunk_size_does_not_change_output() {
+        let input = sine(44100, 0.5);
+        let whole = resample_in_chunks(44100, &input, input.len() / 2);
+        for chunk_frames in [1, 333] {
+            let chunked = resample_in_chunks(44100, &input, chunk_frames);
+            assert_eq!(chunked.len(), whole.len());
+            assert!(chunked.iter().zip(&whole).all(|(a, b)| (a - b).abs() < 1e-5));
+        }
+    }
+}
@@ -0,0 +1,831 @@
+use super::encoder::{Encoder, EncoderStatus, Feed};
+use super::effects::{EffectChain, Effects};
+use super::layer::{decibels, Layer};
+use super::loudness::Measurement;
+use super::server::Channel;
+use super::sound::{PlaySettings, Sound};
+use super::source::AudioSource;
+use super::tap::tap;
+use super::playlist::{Scene, SceneLayer};
+use super::track::{MultiplayerPlaylist, MultiplayerPlaylistMessage, MultiplayerTrack, MultiplayerTrackMessage};
+
+use crate::meter::Levels;
+use crate::protocol::{ChannelLayout, Codec, Frame};
+use crate::settings::{PlaybackMode, StreamQuality};
+use kira::modulator::tweener::{TweenerBuilder, TweenerHandle};
+use kira::sound::{EndPosition, PlaybackPosition, PlaybackState, Region};
+use kira::track::{TrackBuilder, TrackHandle};
+use kira::backend::Backend;
+use kira::{AudioManager, Decibels, Easing, Mapping, StartTime, Tween, Value};
+use std::time::Duration;
+
+// Lowest duck depth the mapping covers, in dB
+const DUCK_FLOOR: f64 = -60.0;
+const DUCK_ATTACK: Duration = Duration::from_millis(150);
+// How long before a crossfade it gets handed to kira
+const SCHEDULE_AHEAD: Duration = Duration::from_millis(250);
+
+// How quickly the oldest sound effect fades out to make room for a new one
+const SFX_STEAL_FADE: Duration = Duration::from_millis(30);
+
+// Half a second of mixer output, so a slow encoder thread doesn't drop audio.
+const TAP_CAPACITY: usize = 24000;
+
+/// One independent stream served by the host: its own playlist, kira tracks and encoder.
+pub struct Room {
+    pub name: String,
+    _bus_track_handle: TrackHandle,
+    master_effects: EffectChain,
+    music_track_handle: TrackHandle,
+    duck_tweener: TweenerHandle,
+    // The current track plays on the main layer, crossfades and ambience on the others
+    layers: Vec<Layer>,
+    // Sound effects play next to the music, so ducking and crossfades leave them alone
+    sfx_track_handle: TrackHandle,
+    sfx_handles: Vec<Sound>,
+    sfx_polyphony: usize,
+    pub playlist: MultiplayerPlaylist,
+    pub playback_position: f64,
+    playback_mode: PlaybackMode,
+    // Target loudness in LUFS tracks are normalized to, if they are
+    normalize_to: Option<f64>,
+    // Tracks still to play in this round of shuffle
+    shuffle_queue: Vec<usize>,
+    feeds: Vec<Feed>,
+    encoder: Encoder,
+}
+
+impl Room {
+    pub fn new<B: Backend>(
+        name: String,
+        layout: ChannelLayout,
+        quality: StreamQuality,
+        audio_manager: &mut AudioManager<B>,
+    ) -> Self {
+        let (tap_builder, tap_receiver) = tap(TAP_CAPACITY);
+        // The master effects come before the tap, so clients hear them
+        let mut bus_builder = TrackBuilder::new();
+        let master_effects = EffectChain::add_to(&mut bus_builder, &Effects::default());
+        bus_builder.add_effect(tap_builder);
+        let mut bus_track = audio_manager.add_sub_track(bus_builder).unwrap();
+
+        // Ducking happens on a track inside the bus, so the tap hears it too
+        let duck_tweener = audio_manager.add_modulator(
+            TweenerBuilder {
+                initial_value: 0.0,
+            }
+        ).unwrap();
+        let music_track = bus_track.add_sub_track(TrackBuilder::new().volume(Value::FromModulator {
+            id: duck_tweener.id(),
+            mapping: Mapping {
+                input_range: (DUCK_FLOOR, 0.0),
+                output_range: (Decibels(DUCK_FLOOR as f32), Decibels::IDENTITY),
+                easing: Easing::Linear,
+            },
+        })).unwrap();
+
+        let sfx_track = bus_track.add_sub_track(TrackBuilder::new()).unwrap();
+
+        let feeds = Codec::ALL.map(Feed::new).to_vec();
+        // The tap is tied to the mixer, so reopening it only starts a fresh receiver on the same ring buffer
+        let encoder = Encoder::spawn(&name, feeds.clone(), layout, quality, move || {
+            Ok(Box::new(tap_receiver.reopen()) as Box<dyn AudioSource>)
+        });
+
+        Self {
+            name,
+            _bus_track_handle: bus_track,
+            master_effects,
+            music_track_handle: music_track,
+            duck_tweener,
+            layers: Vec::new(),
+            sfx_track_handle: sfx_track,
+            sfx_handles: Vec::new(),
+            sfx_polyphony: 1,
+            playlist: MultiplayerPlaylist::new(),
+            playback_position: 0.0,
+            playback_mode: PlaybackMode::default(),
+            normalize_to: None,
+            shuffle_queue: Vec::new(),
+            feeds,
+            encoder,
+        }
+    }
+
+    pub fn channel(&self) -> Channel {
+        Channel {
+            name: self.name.clone(),
+            feeds: self.feeds.clone(),
+        }
+    }
+
+    pub fn set_layout(&self, layout: ChannelLayout) {
+        self.encoder.set_layout(layout);
+    }
+
+    pub fn set_quality(&self, quality: StreamQuality) {
+        self.encoder.set_quality(quality);
+    }
+
+    pub fn set_overlay(&self, overlay: Option<Box<dyn AudioSource + Send>>) {
+        self.encoder.set_overlay(overlay);
+    }
+
+    /// Lowers the music by `depth` dB, or brings it back over `release_ms`.
+    pub fn set_ducked(&mut self, ducked: bool, depth: f32, release_ms: u64) {
+        let (value, duration) = if ducked {
+            ((depth as f64).max(DUCK_FLOOR), DUCK_ATTACK)
+        } else {
+            (0.0, Duration::from_millis(release_ms))
+        };
+        self.duck_tweener.set(
+            value,
+            Tween {
+                start_time: StartTime::Immediate,
+                duration,
+                easing: Easing::Linear,
+            });
+    }
+
+    pub fn encoder_status(&self) -> EncoderStatus {
+        self.encoder.status()
+    }
+
+    pub fn restart_encoder(&self) {
+        self.encoder.restart();
+    }
+
+    pub fn take_levels(&self) -> Levels {
+        self.encoder.take_levels()
+    }
+
+    /// Stops the encoder thread. With a reason, the clients listening to this room are told it closed.
+    pub fn close(&mut self, reason: Option<&str>) {
+        self.encoder.stop();
+        if let Some(reason) = reason {
+            for feed in self.feeds.iter() {
+                let _ = feed.tx_capt.send(Frame::EndSession(reason.to_string()));
+            }
+        }
+    }
+
+    pub fn set_playback_mode(&mut self, playback_mode: PlaybackMode) {
+        self.playback_mode = playback_mode;
+        self.shuffle_queue.clear();
+        if let Some(index) = self.playlist.current_track {
+            self.refresh_loop_region(index);
+        }
+    }
+
+    /// Normalizes every track to `target` LUFS, or turns normalization off.
+    pub fn set_normalization(&mut self, target: Option<f64>) {
+        self.normalize_to = target;
+        for index in 0..self.playlist.tracks.len() {
+            self.refresh_gain(index);
+        }
+    }
+
+    /// Stores the loudness measured for the file at `path` in every track that plays it.
+    pub fn set_loudness(&mut self, path: &str, measurement: Option<Measurement>) {
+        for index in 0..self.playlist.tracks.len() {
+            let track = &mut self.playlist.tracks[index];
+            if track.path == path {
+                track.loudness = measurement.map(|measurement| measurement.loudness);
+                track.peak = measurement.map(|measurement| measurement.peak);
+                track.analyzing = false;
+                self.refresh_gain(index);
+            }
+        }
+    }
+
+    fn gain(&self, track: &MultiplayerTrack) -> Decibels {
+        Decibels(track.normalization_gain(self.normalize_to) as f32)
+    }
+
+    /// Applies the track's normalization gain to it while it plays.
+    fn refresh_gain(&mut self, index: usize) {
+        let Some(track) = self.playlist.get_track(index) else {
+            return;
+        };
+        let gain = track.normalization_gain(self.normalize_to);
+        for layer in self.layers.iter_mut().filter(|layer| layer.index() == index && layer.is_active()) {
+            layer.set_gain(gain, Tween::default());
+        }
+    }
+
+    fn loop_region(&self, track: &MultiplayerTrack) -> Option<Region> {
+        (self.playback_mode == PlaybackMode::LoopTrack).then(|| track.loop_region())
+    }
+
+    /// Applies the track's loop points to it while it plays.
+    fn refresh_loop_region(&mut self, index: usize) {
+        let Some(track) = self.playlist.get_track(index) else {
+            return;
+        };
+        let (main_region, layer_region) = (self.loop_region(track), Some(track.loop_region()));
+        for layer in self.layers.iter_mut().filter(|layer| layer.index() == index && layer.is_active()) {
+            layer.set_loop_region(if layer.is_main() { main_region } else { layer_region });
+        }
+    }
+
+    fn main_layer(&self) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.is_main())
+    }
+
+    fn main_layer_mut(&mut self) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.is_main())
+    }
+
+    fn active_layer_mut(&mut self, index: usize) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.index() == index && layer.is_active())
+    }
+
+    /// The layers that are playing, or waiting to, in the order they were started.
+    pub fn active_layers(&self) -> impl Iterator<Item = &Layer> {
+        self.layers.iter().filter(|layer| layer.is_active())
+    }
+
+    pub fn is_layer_active(&self, index: usize) -> bool {
+        self.active_layers().any(|layer| layer.index() == index)
+    }
+
+    /// Where the track at `index` is playing, if it's playing on any layer.
+    fn position_in(&self, index: usize) -> Option<f64> {
+        self.active_layers()
+            .find(|layer| layer.index() == index)
+            .map(Layer::position)
+    }
+
+    /// Schedules the crossfade into the next track, as the playback mode says, once the current one is within
+    /// `overlap` milliseconds of its end. The crossfade is started by kira at the exact position, so this only
+    /// has to be called more often than `SCHEDULE_AHEAD`. Also lets go of a track that played out.
+    /// A trimmed track that nothing follows fades out over `fade_out_duration` milliseconds instead, ending at its end.
+    pub fn advance(&mut self, overlap: u64, fade_out_duration: u64) {
+        self.layers.retain(|layer| layer.state() != PlaybackState::Stopped);
+        let (state, position) = match self.main_layer() {
+            Some(layer) => (layer.state(), layer.position()),
+            None => {
+                if self.playlist.current_track.take().is_some() {
+                    self.playback_position = 0.0;
+                }
+                return;
+            },
+        };
+        if state != PlaybackState::Playing || self.playback_mode == PlaybackMode::LoopTrack {
+            return;
+        }
+        let Some(track) = self.playlist.get_current_track() else {
+            return;
+        };
+        let end = track.end();
+        let trimmed = end < track.duration();
+        let overlap = if self.has_next_track() { overlap } else { fade_out_duration };
+        // The track's own fade out sets the overlap instead. Never overlap more than half of a short track, or it
+        // would skip right past it
+        let overlap = track.fade_out.map_or(overlap, |fade| fade.duration);
+        let overlap = (overlap as f64 / 1000.0).min((end - track.start_offset) / 2.0);
+        let until_crossfade = end - position - overlap;
+        if until_crossfade > SCHEDULE_AHEAD.as_secs_f64() {
+            return;
+        }
+        let tween = Tween {
+            start_time: StartTime::Delayed(Duration::from_secs_f64(until_crossfade.max(0.0))),
+            duration: Duration::from_secs_f64(overlap),
+            easing: Easing::Linear,
+        };
+        let fade_out = track.fade_out_tween(tween);
+        let fade_out = Tween { duration: tween.duration, ..fade_out };
+        let has_fade_out = track.fade_out.is_some();
+        let Some(next) = self.next_track() else {
+            // Nothing comes next, but a trimmed track still has to stop at its end
+            if (trimmed || has_fade_out) && let Some(layer) = self.main_layer_mut() {
+                layer.fade_out(fade_out);
+            }
+            return;
+        };
+        let Some(next_track) = self.playlist.get_track(next) else {
+            return;
+        };
+        let fade_in = next_track.fade_in_tween(tween);
+        self.playback_position = next_track.start_offset;
+        self.playlist.current_track = Some(next);
+        self.shuffle_queue.retain(|queued| *queued != next);
+        self.crossfade_to(next, fade_out, fade_in);
+    }
+
+    /// Indices of the tracks that aren't sound effects.
+    fn music(&self) -> Vec<usize> {
+        self.playlist.tracks.iter()
+            .enumerate()
+            .filter(|(_, track)| !track.sfx)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Whether `next_track` would find anything, without taking it from the shuffle queue.
+    fn has_next_track(&self) -> bool {
+        let Some(current) = self.playlist.current_track else {
+            return false;
+        };
+        match self.playback_mode {
+            PlaybackMode::LoopTrack | PlaybackMode::PlayOnce => false,
+            PlaybackMode::Advance => self.music().into_iter().any(|index| index > current),
+            PlaybackMode::RepeatPlaylist | PlaybackMode::Shuffle => !self.music().is_empty(),
+        }
+    }
+
+    /// The next track to play, skipping sound effects.
+    fn next_track(&mut self) -> Option<usize> {
+        let current = self.playlist.current_track?;
+        let music = self.music();
+        match self.playback_mode {
+            PlaybackMode::LoopTrack | PlaybackMode::PlayOnce => None,
+            PlaybackMode::Advance => music.into_iter().find(|index| *index > current),
+            PlaybackMode::RepeatPlaylist => music.iter().find(|index| **index > current).or(music.first()).copied(),
+            PlaybackMode::Shuffle => {
+                if self.shuffle_queue.is_empty() {
+                    // A new round, which doesn't start with the track that just ended unless it's the only one
+                    let only_one = music.len() == 1;
+                    self.shuffle_queue = music.into_iter().filter(|index| *index != current || only_one).collect();
+                    fastrand::shuffle(&mut self.shuffle_queue);
+                }
+                self.shuffle_queue.pop()
+            }
+        }
+    }
+
+    pub fn playback_state(&self) -> Option<PlaybackState> {
+        self.main_layer().map(Layer::state)
+    }
+
+    pub fn replace_playlist(&mut self, playlist: MultiplayerPlaylist) {
+        self.shuffle_queue.clear();
+        self.playback_position = 0.0;
+        // Dropping a layer's kira track stops its sound
+        self.layers.clear();
+        self.stop_sfx(Duration::ZERO);
+        self.playlist = playlist;
+        self.playlist.current_track = None;
+        self.master_effects.set(&self.playlist.master_effects);
+    }
+
+    pub fn set_master_effects(&mut self, effects: Effects) {
+        self.playlist.master_effects = effects;
+        self.master_effects.set(&effects);
+    }
+
+    pub fn update(&mut self, message: MultiplayerPlaylistMessage, fade_in_duration: u64, fade_out_duration: u64) {
+        match message {
+            MultiplayerPlaylistMessage::MultiplayerTrack(index, message) => {
+                match message {
+                    MultiplayerTrackMessage::Play(_) if self.playlist.get_track(index).is_some_and(|track| track.sfx) => {
+                        self.trigger_sfx(index);
+                    }
+                    MultiplayerTrackMessage::Play(reset) => {
+                        if self.playlist.current_track.is_some_and(|current_track| current_track == index) && !reset {
+                            return;
+                        }
+                        let mut fade_out = Tween {
+                            start_time: StartTime::Immediate,
+                            duration: Duration::from_millis(fade_out_duration),
+                            easing: Easing::Linear,
+                        };
+                        if let Some(track) = self.playlist.get_current_track() {
+                            fade_out = track.fade_out_tween(fade_out);
+                        }
+                        self.playlist.current_track = Some(index);
+                        self.shuffle_queue.retain(|queued| *queued != index);
+                        let Some(track) = self.playlist.get_current_track() else {
+                            return;
+                        };
+                        self.playback_position = if reset {
+                            track.start_offset
+                        } else {
+                            track.resume_position(self.playback_position)
+                        };
+                        let fade_in = track.fade_in_tween(Tween {
+                            start_time: StartTime::Immediate,
+                            duration: Duration::from_millis(fade_in_duration / 2),
+                            easing: Easing::OutPowi(3),
+                        });
+
+                        self.crossfade_to(index, fade_out, fade_in);
+                    }
+                    MultiplayerTrackMessage::ToggleEditor => {
+                        self.playlist.tracks[index].editing = !self.playlist.tracks[index].editing;
+                    },
+                    MultiplayerTrackMessage::LoopStartChanged(start) => {
+                        self.playlist.tracks[index].set_loop_start(start);
+                        self.refresh_loop_region(index);
+                    },
+                    MultiplayerTrackMessage::LoopEndChanged(end) => {
+                        self.playlist.tracks[index].set_loop_end(end);
+                        self.refresh_loop_region(index);
+                    },
+                    MultiplayerTrackMessage::SetLoopStartHere => {
+                        if let Some(position) = self.position_in(index) {
+                            self.playlist.tracks[index].set_loop_start(position);
+                            self.refresh_loop_region(index);
+                        }
+                    },
+                    MultiplayerTrackMessage::SetLoopEndHere => {
+                        if let Some(position) = self.position_in(index) {
+                            self.playlist.tracks[index].set_loop_end(position);
+                            self.refresh_loop_region(index);
+                        }
+                    },
+                    MultiplayerTrackMessage::ClearLoop => {
+                        self.playlist.tracks[index].loop_start = None;
+                        self.playlist.tracks[index].loop_end = None;
+                        self.refresh_loop_region(index);
+                    },
+                    MultiplayerTrackMessage::CueNameChanged(name) => {
+                        self.playlist.tracks[index].new_cue_name = name;
+                    },
+                    MultiplayerTrackMessage::AddCue => {
+                        let position = self.position_in(index).unwrap_or(0.0);
+                        self.playlist.tracks[index].add_cue(position);
+                    },
+                    MultiplayerTrackMessage::JumpToCue(cue) => {
+                        let Some(position) = self.playlist.tracks[index].cues.get(cue).map(|cue| cue.position) else {
+                            return;
+                        };
+                        if self.position_in(index).is_some() {
+                            self.seek_layer(index, position);
+                        } else {
+                            self.playback_position = position;
+                            self.update(
+                                MultiplayerPlaylistMessage::MultiplayerTrack(index, MultiplayerTrackMessage::Play(false)),
+                                fade_in_duration,
+                                fade_out_duration,
+                            );
+                        }
+                    },
+                    MultiplayerTrackMessage::RemoveCue(cue) => {
+                        if cue < self.playlist.tracks[index].cues.len() {
+                            self.playlist.tracks[index].cues.remove(cue);
+                        }
+                    },
+                    MultiplayerTrackMessage::StartOffsetChanged(start) => {
+                        self.playlist.tracks[index].set_start_offset(start);
+                        self.refresh_loop_region(index);
+                    },
+                    MultiplayerTrackMessage::TrimEndChanged(end) => {
+                        self.playlist.tracks[index].set_trim_end(end);
+                        self.refresh_loop_region(index);
+                    },
+                    MultiplayerTrackMessage::FadeInChanged(fade) => {
+                        self.playlist.tracks[index].fade_in = fade;
+                    },
+                    MultiplayerTrackMessage::FadeOutChanged(fade) => {
+                        self.playlist.tracks[index].fade_out = fade;
+                    },
+                    MultiplayerTrackMessage::ToggleLayer => {
+                        let Some(main) = self.active_layers().find(|layer| layer.index() == index).map(Layer::is_main) else {
+                            self.start_layer(index, fade_in_duration);
+                            return;
+                        };
+                        let fade_out = self.playlist.tracks[index].fade_out_tween(Tween {
+                            start_time: StartTime::Immediate,
+                            duration: Duration::from_millis(fade_out_duration),
+                            easing: Easing::Linear,
+                        });
+                        if let Some(layer) = self.active_layer_mut(index) {
+                            layer.fade_out(fade_out);
+                        }
+                        if main {
+                            self.playlist.current_track = None;
+                            self.playback_position = 0.0;
+                        }
+                    },
+                    MultiplayerTrackMessage::ToggleSfx => {
+                        self.playlist.tracks[index].sfx = !self.playlist.tracks[index].sfx;
+                        self.shuffle_queue.retain(|queued| *queued != index);
+                    },
+                    MultiplayerTrackMessage::EffectsChanged(effects) => {
+                        self.playlist.tracks[index].effects = effects;
+                        for layer in self.layers.iter_mut().filter(|layer| layer.index() == index) {
+                            layer.set_effects(&effects);
+                        }
+                    },
+                    MultiplayerTrackMessage::UpdateVolumeSlider(new_volume) => {
+                        self.playlist.tracks[index].volume = new_volume;
+                        if let Some(layer) = self.active_layer_mut(index) {
+                            layer.set_volume(
+                                new_volume,
+                                Tween {
+                                    start_time: StartTime::Immediate,
+                                    duration: Duration::from_millis(0),
+                                    easing: Easing::Linear,
+                                });
+                        }
+                    },
+                    MultiplayerTrackMessage::Remove => {
+                        // Dropping a layer's kira track stops its sound
+                        self.layers.retain(|layer| layer.index() != index);
+                        for layer in self.layers.iter_mut().filter(|layer| layer.index() > index) {
+                            layer.set_index(layer.index() - 1);
+                        }
+                        if self.playlist.current_track.is_some_and(|current_track| current_track == index ) {
+                            self.playlist.current_track = None;
+                            self.playback_position = 0.0;
+                        }
+                        else if self.playlist.current_track.is_some() && index < self.playlist.current_track.unwrap() {
+                            self.playlist.current_track = Some(self.playlist.current_track.unwrap() - 1);
+                        }
+                        self.playlist.remove_track(index);
+                        self.shuffle_queue.retain(|queued| *queued != index);
+                        for queued in self.shuffle_queue.iter_mut().filter(|queued| **queued > index) {
+                            *queued -= 1;
+                        }
+                    },
+                    MultiplayerTrackMessage::MoveTrackUp => {
+                        if index != 0 {
+                            self.playlist.swap_tracks(index, index - 1);
+                            self.swap_queued(index, index - 1);
+                            if let Some(current_track) = self.playlist.current_track {
+                                if current_track == index {
+                                    self.playlist.current_track = Some(index - 1);
+                                }
+                                else if current_track == index - 1 {
+                                    self.playlist.current_track = Some(index);
+                                }
+                            }
+                        }
+                    },
+                    MultiplayerTrackMessage::MoveTrackDown => {
+                        if index != self.playlist.tracks.len() - 1 {
+                            self.playlist.swap_tracks(index, index + 1);
+                            self.swap_queued(index, index + 1);
+                            if let Some(current_track) = self.playlist.current_track {
+                                if current_track == index {
+                                    self.playlist.current_track = Some(index + 1);
+                                }
+                                else if current_track == index + 1 {
+                                    self.playlist.current_track = Some(index);
+                                }
+                            }
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    fn swap_queued(&mut self, first: usize, second: usize) {
+        for queued in self.shuffle_queue.iter_mut() {
+            if *queued == first {
+                *queued = second;
+            } else if *queued == second {
+                *queued = first;
+            }
+        }
+        for layer in self.layers.iter_mut() {
+            if layer.index() == first {
+                layer.set_index(second);
+            } else if layer.index() == second {
+                layer.set_index(first);
+            }
+        }
+    }
+
+    /// Plays the track at `index` once over the music, again each time it's triggered. Past the polyphony limit
+    /// the oldest sound effect still playing makes room.
+    fn trigger_sfx(&mut self, index: usize) {
+        let Some(track) = self.playlist.get_track(index) else {
+            return;
+        };
+        let settings = PlaySettings {
+            slice: Some(Region {
+                start: PlaybackPosition::Seconds(track.start_offset),
+                end: track.trim_end.map_or(EndPosition::EndOfAudio, |end| EndPosition::Custom(PlaybackPosition::Seconds(end))),
+            }),
+            volume: if track.volume > 0.0 {
+                Decibels(decibels(track.volume).0 + self.gain(track).0)
+            } else {
+                Decibels::SILENCE
+            },
+            ..PlaySettings::default()
+        };
+        self.sfx_handles.retain(|handle| handle.state() != PlaybackState::Stopped);
+        while self.sfx_handles.len() >= self.sfx_polyphony.max(1) {
+            let mut oldest = self.sfx_handles.remove(0);
+            oldest.stop(Tween {
+                start_time: StartTime::Immediate,
+                duration: SFX_STEAL_FADE,
+                easing: Easing::Linear,
+            });
+        }
+        if let Some(sound) = track.data.play(&mut self.sfx_track_handle, settings) {
+            self.sfx_handles.push(sound);
+        }
+    }
+
+    fn stop_sfx(&mut self, fade_out: Duration) {
+        for mut handle in self.sfx_handles.drain(..) {
+            handle.stop(Tween {
+                start_time: StartTime::Immediate,
+                duration: fade_out,
+                easing: Easing::Linear,
+            });
+        }
+    }
+
+    /// Volume of all sound effects together, on top of each one's own.
+    pub fn set_sfx_volume(&mut self, volume: f64) {
+        self.sfx_track_handle.set_volume(decibels(volume), Tween::default());
+    }
+
+    /// How many sound effects can play at once.
+    pub fn set_sfx_polyphony(&mut self, polyphony: usize) {
+        self.sfx_polyphony = polyphony;
+    }
+
+    /// Starts the track at `index` looping on a layer of its own, over whatever else is playing.
+    fn start_layer(&mut self, index: usize, fade_in_duration: u64) {
+        let Some(track) = self.playlist.get_track(index) else {
+            return;
+        };
+        let fade_in = track.fade_in_tween(Tween {
+            start_time: StartTime::Immediate,
+            duration: Duration::from_millis(fade_in_duration),
+            easing: Easing::Linear,
+        });
+        self.start_layer_at(index, false, track.start_offset, fade_in);
+    }
+
+    fn start_layer_at(&mut self, index: usize, main: bool, position: f64, fade_in: Tween) {
+        let Some(track) = self.playlist.get_track(index) else {
+            return;
+        };
+        let loop_region = if main { self.loop_region(track) } else { Some(track.loop_region()) };
+        let settings = PlaySettings {
+            start_position: position,
+            loop_region,
+            volume: self.gain(track),
+            ..PlaySettings::default()
+        };
+        let layer = Layer::start(&mut self.music_track_handle, index, main, track, settings, fade_in);
+        self.layers.extend(layer);
+    }
+
+    /// Saves what's playing as a scene, replacing the scene with the same name.
+    pub fn save_scene(&mut self, name: String) {
+        let layers = self.active_layers()
+            .filter_map(|layer| {
+                let track = self.playlist.get_track(layer.index())?;
+                Some(SceneLayer {
+                    path: track.path.clone(),
+                    position: layer.position(),
+                    volume: track.volume,
+                    main: layer.is_main(),
+                })
+            })
+            .collect();
+        let scene = Scene {
+            name,
+            layers,
+        };
+        match self.playlist.scenes.iter_mut().find(|saved| saved.name == scene.name) {
+            Some(saved) => *saved = scene,
+            None => self.playlist.scenes.push(scene),
+        }
+    }
+
+    pub fn remove_scene(&mut self, index: usize) {
+        if index < self.playlist.scenes.len() {
+            self.playlist.scenes.remove(index);
+        }
+    }
+
+    /// Crossfades from what's playing to the scene over `transition` milliseconds. Tracks in both keep playing
+    /// and only move to the scene's volume, the others fade out, or fade in from where the scene left them.
+    pub fn recall_scene(&mut self, index: usize, transition: u64) {
+        let Some(scene) = self.playlist.scenes.get(index).cloned() else {
+            return;
+        };
+        let tween = Tween {
+            start_time: StartTime::Immediate,
+            duration: Duration::from_millis(transition),
+            easing: Easing::Linear,
+        };
+        // Tracks that were removed from the playlist since are left out
+        let targets = scene.layers.iter()
+            .filter_map(|scene_layer| {
+                self.playlist.tracks.iter()
+                    .position(|track| track.path == scene_layer.path && !track.sfx)
+                    .map(|index| (index, scene_layer))
+            })
+            .collect::<Vec<(usize, &SceneLayer)>>();
+        for layer in self.layers.iter_mut().filter(|layer| layer.is_active()) {
+            if !targets.iter().any(|(index, _)| *index == layer.index()) {
+                layer.fade_out(tween);
+            }
+        }
+        self.playlist.current_track = None;
+        self.playback_position = 0.0;
+        for (index, scene_layer) in targets {
+            self.playlist.tracks[index].volume = scene_layer.volume;
+            match self.active_layer_mut(index) {
+                Some(layer) => {
+                    layer.set_main(scene_layer.main);
+                    layer.set_volume(scene_layer.volume, tween);
+                },
+                None => self.start_layer_at(index, scene_layer.main, scene_layer.position, tween),
+            }
+            if scene_layer.main {
+                self.playlist.current_track = Some(index);
+                self.playback_position = scene_layer.position;
+            }
+            self.refresh_loop_region(index);
+        }
+    }
+
+    /// Starts the track at `index` from `playback_position` on a new main layer and fades the old main layer out,
+    /// along with the track if it was already playing as a layer of its own. Both tweens share a start time,
+    /// so a delayed crossfade happens as one.
+    fn crossfade_to(&mut self, index: usize, fade_out: Tween, fade_in: Tween) {
+        let Some(track) = self.playlist.get_track(index) else {
+            return;
+        };
+        let settings = PlaySettings {
+            start_position: self.playback_position,
+            start_time: fade_in.start_time,
+            loop_region: self.loop_region(track),
+            volume: self.gain(track),
+            ..PlaySettings::default()
+        };
+        for layer in self.layers.iter_mut().filter(|layer| layer.is_main() || (layer.index() == index && layer.is_active())) {
+            layer.fade_out(fade_out);
+        }
+        let layer = Layer::start(&mut self.music_track_handle, index, true, track, settings, fade_in);
+        self.layers.extend(layer);
+    }
+
+    /// Moves whichever layer plays the track at `index` to `position`.
+    fn seek_layer(&mut self, index: usize, position: f64) {
+        if let Some(layer) = self.active_layer_mut(index) {
+            layer.seek_to(position);
+        }
+    }
+
+    pub fn seek(&mut self) {
+        let position = self.playback_position;
+        if let Some(layer) = self.main_layer_mut() {
+            layer.seek_to(position);
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if let Some(layer) = self.main_layer() {
+            self.playback_position = layer.position();
+        }
+    }
+
+    pub fn pause(&mut self, fade_out_duration: u64) {
+        if let Some(layer) = self.main_layer_mut() {
+            layer.pause(Tween {
+                start_time: StartTime::Immediate,
+                duration: Duration::from_millis(fade_out_duration),
+                easing: Easing::Linear,
+            })
+        }
+    }
+
+    pub fn resume(&mut self, fade_in_duration: u64) {
+        if let Some(layer) = self.main_layer_mut() {
+            layer.resume(Tween {
+                start_time: StartTime::Immediate,
+                duration: Duration::from_millis(fade_in_duration),
+                easing: Easing::Linear,
+            })
+        }
+    }
+
+    /// Fades out the current track. Layers started on top of it keep playing.
+    pub fn stop(&mut self, fade_out_duration: u64) {
+        if let Some(layer) = self.main_layer_mut() {
+            layer.fade_out(Tween {
+                start_time: StartTime::Immediate,
+                duration: Duration::from_millis(fade_out_duration),
+                easing: Easing::Linear,
+            });
+            self.playlist.current_track = None;
+        }
+        self.playback_position = 0.0;
+    }
+
+    /// Fades out every layer, the current track included, and any sound effects.
+    pub fn stop_all(&mut self, fade_out_duration: u64) {
+        self.stop_sfx(Duration::from_millis(fade_out_duration));
+        for layer in self.layers.iter_mut().filter(|layer| layer.is_active()) {
+            layer.fade_out(Tween {
+                start_time: StartTime::Immediate,
+                duration: Duration::from_millis(fade_out_duration),
+                easing: Easing::Linear,
+            });
+        }
+        self.playlist.current_track = None;
+        self.playback_position = 0.0;
+    }
+}
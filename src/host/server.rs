@@ -1,22 +1,42 @@
-use opus::Bitrate;
-use opus::ErrorCode as OpusErrorCode;
-use std::collections::{HashMap, VecDeque};
+use super::encoder::Feed;
+
+use crate::protocol::{Frame, Join};
+use bytes::BytesMut;
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
-use std::{error, thread};
-use sysinfo::{get_current_pid, Pid};
+use std::time::Duration;
 use tokio::io;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpListener;
-use std::thread::JoinHandle;
-use wasapi::{initialize_mta, AudioClient, Direction, SampleType, StreamMode, WaveFormat};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinSet;
+use tokio::time::timeout;
 
 const HOST_PORT: u16 = 9475;
-const CAPTURE_CHUNK_SIZE: usize = 480;
-const BIT_RATE: i32 = 64000;
-const CHANNELS: u16 = 2;
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+const JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A named stream clients can pick during the handshake, with one feed per codec.
+#[derive(Clone)]
+pub struct Channel {
+    pub name: String,
+    pub feeds: Vec<Feed>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectedClient {
+    pub username: String,
+    pub channel: String,
+}
 
-pub async fn run(clients: Arc<Mutex<HashMap<SocketAddr, String>>>, tx_capt: tokio::sync::broadcast::Sender<Vec<u8>>) -> io::Result<()> {
+/// Serves the broadcast to every connected client until `shutdown` carries a reason,
+/// then tells each client why the session ended and closes its socket.
+pub async fn run(
+    clients: Arc<Mutex<HashMap<SocketAddr, ConnectedClient>>>,
+    channels: Arc<Mutex<Vec<Channel>>>,
+    mut shutdown: watch::Receiver<Option<String>>,
+) -> io::Result<()> {
     // let gateway_ip = match reqwest::blocking::get("https://api.ipify.org") {
     //     Ok(response) => {
     //         let ip = response.text().unwrap();
@@ -39,56 +59,145 @@ pub async fn run(clients: Arc<Mutex<HashMap<SocketAddr, String>>>, tx_capt: toki
     };
     let listener = TcpListener::bind(format!("{ip}:{HOST_PORT}")).await?;
 
-    let tx_capt_clone = tx_capt.clone();
+    let mut sessions = JoinSet::new();
 
 
     println!("Listening on port {}", HOST_PORT);
 
     loop {
-        let (mut stream, addr) = listener.accept().await?;
-        
-        let mut rx = tx_capt_clone.subscribe();
-        let clients_clone = clients.clone();
-
-        tokio::spawn(async move {
-            {
-                // Read the first packet to get the client's name
-                let mut buf = [0u8; 1024];
-                let bytes_read = stream.read(&mut buf).await.unwrap();
-                let username = String::from_utf8_lossy(&buf[..bytes_read]).to_string();
-                
-                let mut clients = clients_clone.lock().unwrap();
-                clients.insert(addr, username);
-                println!("Client connected: {}", addr);
-                println!("Clients: {:?}", clients);
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, addr) = accepted?;
+                sessions.spawn(serve_client(stream, addr, clients.clone(), channels.clone(), shutdown.clone()));
             }
-            loop {
-                match rx.recv().await {
-                    Ok(data) => {
-                        match stream.try_write(data.as_slice()) {
-                            Ok(_n) => {
-                            }
-                            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                                continue;
-                            }
-                            Err(e) => {
-                                println!("Error trying to write data as slice: {}", e);
-                                {
-                                    let mut clients = clients_clone.lock().unwrap();
-                                    clients.remove(&addr);
-                                    println!("Client disconnected: {}", addr);
-                                    println!("Clients: {:?}", clients);
-                                }
-                                break;
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        println!("Server RecvError: {}", err);
-                        return;
+            _ = shutdown.changed() => {
+                println!("Shutting down server");
+                break;
+            }
+        }
+    }
+
+    drop(listener);
+    while sessions.join_next().await.is_some() {}
+    Ok(())
+}
+
+async fn serve_client(
+    mut stream: TcpStream,
+    addr: SocketAddr,
+    clients: Arc<Mutex<HashMap<SocketAddr, ConnectedClient>>>,
+    channels: Arc<Mutex<Vec<Channel>>>,
+    mut shutdown: watch::Receiver<Option<String>>,
+) {
+    let join = match timeout(JOIN_TIMEOUT, read_join(&mut stream)).await {
+        Ok(Ok(join)) => join,
+        Ok(Err(e)) => {
+            println!("Error reading join from {}: {}", addr, e);
+            return;
+        }
+        Err(_) => {
+            println!("Client {} did not join in time", addr);
+            return;
+        }
+    };
+    let channel = {
+        let channels = channels.lock().unwrap();
+        channels.iter()
+            .find(|channel| join.channel.is_empty() || channel.name.eq_ignore_ascii_case(join.channel.trim()))
+            .cloned()
+            .ok_or_else(|| {
+                let names = channels.iter().map(|channel| channel.name.as_str()).collect::<Vec<&str>>();
+                format!("Unknown channel \"{}\", available channels: {}", join.channel, names.join(", "))
+            })
+    };
+    let channel = match channel {
+        Ok(channel) => channel,
+        Err(reason) => {
+            println!("Rejecting {}: {}", addr, reason);
+            let _ = timeout(FLUSH_TIMEOUT, stream.write_all(&Frame::EndSession(reason).encode())).await;
+            let _ = stream.shutdown().await;
+            return;
+        }
+    };
+    let Some(feed) = channel.feeds.iter().find(|feed| feed.codec == join.codec).cloned() else {
+        println!("Rejecting {}: channel {} has no {} feed", addr, channel.name, join.codec);
+        let reason = format!("Channel {} doesn't stream {}", channel.name, join.codec);
+        let _ = timeout(FLUSH_TIMEOUT, stream.write_all(&Frame::EndSession(reason).encode())).await;
+        let _ = stream.shutdown().await;
+        return;
+    };
+    let mut rx = feed.tx_capt.subscribe();
+    {
+        let mut clients = clients.lock().unwrap();
+        clients.insert(addr, ConnectedClient {
+            username: join.username,
+            channel: channel.name,
+        });
+        println!("Client connected: {} ({})", addr, join.codec);
+        println!("Clients: {:?}", clients);
+    }
+    // Subscribed first, so a change after this is still seen as a frame on `rx`
+    let parameters = feed.parameters.lock().unwrap().clone();
+    if let Some(parameters) = parameters
+        && let Err(e) = stream.write_all(&Frame::Stream(parameters).encode()).await
+    {
+        println!("Error writing stream parameters to {}: {}", addr, e);
+    }
+    loop {
+        tokio::select! {
+            received = rx.recv() => match received {
+                Ok(frame) => {
+                    if let Err(e) = stream.write_all(&frame.encode()).await {
+                        println!("Error writing frame to {}: {}", addr, e);
+                        break;
                     }
                 }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    println!("Client {} lagged behind by {} frames", addr, skipped);
+                }
+                Err(err) => {
+                    println!("Server RecvError: {}", err);
+                    break;
+                }
+            },
+            _ = shutdown.changed() => {
+                let reason = shutdown.borrow().clone();
+                if let Some(reason) = reason {
+                    let _ = timeout(FLUSH_TIMEOUT, end_session(&mut stream, &mut rx, reason)).await;
+                }
+                break;
             }
-        });
+        }
+    }
+    {
+        let mut clients = clients.lock().unwrap();
+        clients.remove(&addr);
+        println!("Client disconnected: {}", addr);
+        println!("Clients: {:?}", clients);
+    }
+}
+
+async fn read_join(stream: &mut TcpStream) -> anyhow::Result<Join> {
+    let mut buffer = BytesMut::with_capacity(256);
+    loop {
+        if let Some(frame) = Frame::decode(&mut buffer)? {
+            match frame {
+                Frame::Join(join) => return Ok(join),
+                frame => anyhow::bail!("Expected a join frame, got {:?}", frame),
+            }
+        }
+        if stream.read_buf(&mut buffer).await? == 0 {
+            anyhow::bail!("Connection closed before joining");
+        }
+    }
+}
+
+async fn end_session(stream: &mut TcpStream, rx: &mut broadcast::Receiver<Frame>, reason: String) -> io::Result<()> {
+    // Flush whatever the capture thread queued before it was stopped
+    while let Ok(frame) = rx.try_recv() {
+        stream.write_all(&frame.encode()).await?;
     }
-}
\ No newline at end of file
+    stream.write_all(&Frame::EndSession(reason).encode()).await?;
+    stream.flush().await?;
+    stream.shutdown().await
+}
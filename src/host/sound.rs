@@ -0,0 +1,170 @@
+use kira::sound::static_sound::{StaticSoundData, StaticSoundHandle};
+use kira::sound::streaming::{StreamingSoundData, StreamingSoundHandle};
+use kira::sound::{FromFileError, PlaybackPosition, PlaybackState, Region};
+use kira::track::TrackHandle;
+use kira::{Decibels, StartTime, Tween};
+use std::time::Duration;
+
+/// Files longer than this are streamed from disk instead of decoded into memory. Ten minutes of decoded stereo
+/// already take a couple of hundred megabytes.
+pub const STREAMING_THRESHOLD: Duration = Duration::from_secs(10 * 60);
+
+/// A track's audio: decoded into memory, or for long files, where to stream it from. kira can't share a
+/// streaming sound between plays, so every play of a streamed file opens it again.
+#[derive(Debug, Clone)]
+pub enum SoundData {
+    Static(Box<StaticSoundData>),
+    Streaming {
+        path: String,
+        duration: Duration,
+    },
+}
+
+/// How a sound starts playing, whichever kind it is.
+pub struct PlaySettings {
+    pub start_position: f64,
+    pub start_time: StartTime,
+    pub loop_region: Option<Region>,
+    /// Plays only this part of the file, as if it were all there was.
+    pub slice: Option<Region>,
+    pub volume: Decibels,
+}
+
+impl Default for PlaySettings {
+    fn default() -> Self {
+        Self {
+            start_position: 0.0,
+            start_time: StartTime::Immediate,
+            loop_region: None,
+            slice: None,
+            volume: Decibels::IDENTITY,
+        }
+    }
+}
+
+impl SoundData {
+    /// Opens the file at `path`, only decoding the whole of it if it's shorter than the streaming threshold.
+    pub fn from_file(path: &str) -> Result<Self, FromFileError> {
+        // Opening a stream only reads the file's header, so it's a cheap way to learn how long it is
+        if let Ok(streaming) = StreamingSoundData::from_file(path) {
+            let duration = streaming.duration();
+            if duration >= STREAMING_THRESHOLD {
+                return Ok(Self::Streaming {
+                    path: path.to_string(),
+                    duration,
+                });
+            }
+        }
+        StaticSoundData::from_file(path).map(|data| Self::Static(Box::new(data)))
+    }
+
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::Static(data) => data.duration(),
+            Self::Streaming { duration, .. } => *duration,
+        }
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        matches!(self, Self::Streaming { .. })
+    }
+
+    /// Plays the sound on `track`. None if a streamed file can't be opened any more or kira can't take another sound.
+    pub fn play(&self, track: &mut TrackHandle, settings: PlaySettings) -> Option<Sound> {
+        match self {
+            Self::Static(data) => {
+                let data = data.slice(settings.slice)
+                    .start_position(PlaybackPosition::Seconds(settings.start_position))
+                    .start_time(settings.start_time)
+                    .loop_region(settings.loop_region)
+                    .volume(settings.volume);
+                track.play(data)
+                    .map(Sound::Static)
+                    .map_err(|e| println!("Couldn't play a sound: {}", e))
+                    .ok()
+            }
+            Self::Streaming { path, .. } => {
+                let data = match StreamingSoundData::from_file(path) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        println!("Couldn't stream {}: {}", path, e);
+                        return None;
+                    }
+                };
+                let data = data.slice(settings.slice)
+                    .start_position(PlaybackPosition::Seconds(settings.start_position))
+                    .start_time(settings.start_time)
+                    .loop_region(settings.loop_region)
+                    .volume(settings.volume);
+                track.play(data)
+                    .map(Sound::Streaming)
+                    .map_err(|e| println!("Couldn't play {}: {}", path, e))
+                    .ok()
+            }
+        }
+    }
+}
+
+/// A playing sound, decoded or streamed.
+pub enum Sound {
+    Static(StaticSoundHandle),
+    Streaming(StreamingSoundHandle<FromFileError>),
+}
+
+impl Sound {
+    pub fn state(&self) -> PlaybackState {
+        match self {
+            Self::Static(handle) => handle.state(),
+            Self::Streaming(handle) => handle.state(),
+        }
+    }
+
+    pub fn position(&self) -> f64 {
+        match self {
+            Self::Static(handle) => handle.position(),
+            Self::Streaming(handle) => handle.position(),
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: Decibels, tween: Tween) {
+        match self {
+            Self::Static(handle) => handle.set_volume(volume, tween),
+            Self::Streaming(handle) => handle.set_volume(volume, tween),
+        }
+    }
+
+    pub fn set_loop_region(&mut self, loop_region: Option<Region>) {
+        match self {
+            Self::Static(handle) => handle.set_loop_region(loop_region),
+            Self::Streaming(handle) => handle.set_loop_region(loop_region),
+        }
+    }
+
+    pub fn seek_to(&mut self, position: f64) {
+        match self {
+            Self::Static(handle) => handle.seek_to(position),
+            Self::Streaming(handle) => handle.seek_to(position),
+        }
+    }
+
+    pub fn pause(&mut self, tween: Tween) {
+        match self {
+            Self::Static(handle) => handle.pause(tween),
+            Self::Streaming(handle) => handle.pause(tween),
+        }
+    }
+
+    pub fn resume(&mut self, tween: Tween) {
+        match self {
+            Self::Static(handle) => handle.resume(tween),
+            Self::Streaming(handle) => handle.resume(tween),
+        }
+    }
+
+    pub fn stop(&mut self, tween: Tween) {
+        match self {
+            Self::Static(handle) => handle.stop(tween),
+            Self::Streaming(handle) => handle.stop(tween),
+        }
+    }
+}
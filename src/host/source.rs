@@ -0,0 +1,50 @@
+pub mod file;
+pub mod format;
+pub mod microphone;
+pub mod silence;
+pub mod sine;
+
+use super::resample::OUTPUT_SAMPLE_RATE;
+use std::time::Instant;
+
+/// Something the encoder can pull 48 kHz interleaved stereo float samples from.
+pub trait AudioSource {
+    /// Appends whatever audio became available since the last call to `output`.
+    /// Should return quickly, the encoder calls this in a polling loop.
+    fn read(&mut self, output: &mut Vec<f32>) -> anyhow::Result<()>;
+}
+
+/// Keeps sources that generate their audio at real-time pace.
+pub struct Pacer {
+    sample_rate: u32,
+    started: Instant,
+    frames_produced: u64,
+}
+
+impl Default for Pacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pacer {
+    pub fn new() -> Self {
+        Self::with_sample_rate(OUTPUT_SAMPLE_RATE)
+    }
+
+    pub fn with_sample_rate(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            started: Instant::now(),
+            frames_produced: 0,
+        }
+    }
+
+    /// How many frames are owed to keep up with the wall clock since the pacer was created.
+    pub fn frames_due(&mut self) -> usize {
+        let due = (self.started.elapsed().as_secs_f64() * self.sample_rate as f64) as u64;
+        let frames = due.saturating_sub(self.frames_produced);
+        self.frames_produced = due.max(self.frames_produced);
+        frames as usize
+    }
+}
@@ -0,0 +1,53 @@
+use super::{AudioSource, Pacer};
+use crate::host::resample::Resampler;
+use kira::sound::static_sound::StaticSoundData;
+
+/// Plays an audio file (WAV, FLAC, MP3, OGG) at real-time pace, optionally looping it.
+pub struct FileSource {
+    data: StaticSoundData,
+    position: usize,
+    looping: bool,
+    resampler: Resampler,
+    scratch: Vec<f32>,
+    pacer: Pacer,
+}
+
+impl FileSource {
+    pub fn new(path: &str, looping: bool) -> anyhow::Result<Self> {
+        let data = StaticSoundData::from_file(path)?;
+        let pacer = Pacer::with_sample_rate(data.sample_rate);
+        Ok(Self {
+            data,
+            position: 0,
+            looping,
+            resampler: Resampler::new(),
+            scratch: Vec::new(),
+            pacer,
+        })
+    }
+
+    pub fn finished(&self) -> bool {
+        !self.looping && self.position >= self.data.frames.len()
+    }
+}
+
+impl AudioSource for FileSource {
+    fn read(&mut self, output: &mut Vec<f32>) -> anyhow::Result<()> {
+        let due = self.pacer.frames_due();
+        self.scratch.clear();
+        for _ in 0..due {
+            if self.position >= self.data.frames.len() {
+                if !self.looping || self.data.frames.is_empty() {
+                    break;
+                }
+                self.position = 0;
+            }
+            let frame = self.data.frames[self.position];
+            self.scratch.push(frame.left);
+            self.scratch.push(frame.right);
+            self.position += 1;
+        }
+        self.resampler.process(self.data.sample_rate, &self.scratch, output);
+        Ok(())
+    }
+}
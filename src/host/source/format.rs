@@ -0,0 +1,97 @@
+/// Little-endian PCM sample layouts a capture device can deliver.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SampleFormat {
+    Int16,
+    /// Packed into three bytes.
+    Int24,
+    Int32,
+    Float32,
+}
+
+impl SampleFormat {
+    pub const fn bytes_per_sample(&self) -> usize {
+        match self {
+            Self::Int16 => 2,
+            Self::Int24 => 3,
+            Self::Int32 | Self::Float32 => 4,
+        }
+    }
+
+    pub const fn bits_per_sample(&self) -> usize {
+        self.bytes_per_sample() * 8
+    }
+
+    // Integers are scaled by their negative full scale, so the lowest value maps to exactly -1.0
+    fn to_float_fn(self) -> fn(&[u8]) -> f32 {
+        match self {
+            Self::Int16 => |x| i16::from_le_bytes([x[0], x[1]]) as f32 / 32768.0,
+            // Put the three bytes in the top of an i32 and shift back down to sign-extend
+            Self::Int24 => |x| (i32::from_le_bytes([0, x[0], x[1], x[2]]) >> 8) as f32 / 8388608.0,
+            Self::Int32 => |x| (i32::from_le_bytes([x[0], x[1], x[2], x[3]]) as f64 / 2147483648.0) as f32,
+            Self::Float32 => |x| f32::from_le_bytes([x[0], x[1], x[2], x[3]]),
+        }
+    }
+
+    pub fn to_float_samples(&self, samples: &[u8]) -> anyhow::Result<Vec<f32>> {
+        let len = self.bytes_per_sample();
+        if !samples.len().is_multiple_of(len) {
+            anyhow::bail!("Invalid number of samples {}", samples.len());
+        }
+
+        let conversion = self.to_float_fn();
+
+        let samples = samples.chunks(len).map(conversion).collect();
+        Ok(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn int16_scaling() {
+        let bytes = [i16::MIN, -16384, 0, 16384, i16::MAX]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let samples = SampleFormat::Int16.to_float_samples(&bytes).unwrap();
+        assert_eq!(samples[..4], [-1.0, -0.5, 0.0, 0.5]);
+        assert!((samples[4] - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn int24_scaling_and_sign() {
+        let bytes = [-8388608i32, -4194304, -1, 0, 4194304, 8388607]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes()[..3].to_vec())
+            .collect::<Vec<u8>>();
+        let samples = SampleFormat::Int24.to_float_samples(&bytes).unwrap();
+        assert_eq!(samples[..5], [-1.0, -0.5, -1.0 / 8388608.0, 0.0, 0.5]);
+        assert!((samples[5] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn int32_scaling() {
+        let bytes = [i32::MIN, -1073741824, 0, 1073741824, i32::MAX]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let samples = SampleFormat::Int32.to_float_samples(&bytes).unwrap();
+        assert_eq!(samples, [-1.0, -0.5, 0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn float32_is_unchanged() {
+        let bytes = [-1.0f32, 0.25, 0.999]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<u8>>();
+        assert_eq!(SampleFormat::Float32.to_float_samples(&bytes).unwrap(), [-1.0, 0.25, 0.999]);
+    }
+
+    #[test]
+    fn rejects_partial_samples() {
+        assert!(SampleFormat::Int24.to_float_samples(&[0; 4]).is_err());
+    }
+}
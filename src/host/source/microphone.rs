@@ -0,0 +1,162 @@
+use super::AudioSource;
+use crate::host::resample::Resampler;
+use crate::meter::to_decibels;
+use crate::settings::TalkMode;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+// A tenth of a second of stereo at the device rate is plenty, the encoders poll every few milliseconds
+const RECEIVER_CAPACITY: usize = 9600;
+// How long voice activation stays open after the voice drops below the threshold
+const VOICE_HANG_SECONDS: f32 = 0.4;
+
+struct Shared {
+    push_to_talk: AtomicBool,
+    voice_activation: AtomicBool,
+    threshold_db: AtomicU32,
+    open: AtomicBool,
+    sample_rate: u32,
+    producers: Mutex<Vec<Producer<f32>>>,
+}
+
+/// The default input device, gated by push-to-talk or voice activation.
+/// While the gate is open, its audio goes to every `MicReceiver` as stereo.
+pub struct Microphone {
+    _stream: cpal::Stream,
+    shared: Arc<Shared>,
+}
+
+impl Microphone {
+    pub fn open(mode: TalkMode, threshold_db: f32) -> anyhow::Result<Self> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No microphone found"))?;
+        let config = device.default_input_config()?;
+        let sample_format = config.sample_format();
+        let config: cpal::StreamConfig = config.into();
+
+        let shared = Arc::new(Shared {
+            push_to_talk: AtomicBool::new(false),
+            voice_activation: AtomicBool::new(mode == TalkMode::VoiceActivation),
+            threshold_db: AtomicU32::new(threshold_db.to_bits()),
+            open: AtomicBool::new(false),
+            sample_rate: config.sample_rate.0,
+            producers: Mutex::new(Vec::new()),
+        });
+        let stream = match sample_format {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, shared.clone())?,
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, shared.clone())?,
+            cpal::SampleFormat::I32 => build_stream::<i32>(&device, &config, shared.clone())?,
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, shared.clone())?,
+            format => anyhow::bail!("Unsupported microphone sample format {}", format),
+        };
+        stream.play()?;
+
+        Ok(Self {
+            _stream: stream,
+            shared,
+        })
+    }
+
+    pub fn set_mode(&self, mode: TalkMode) {
+        self.shared.voice_activation.store(mode == TalkMode::VoiceActivation, Ordering::Relaxed);
+    }
+
+    pub fn set_threshold(&self, threshold_db: f32) {
+        self.shared.threshold_db.store(threshold_db.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Holds the gate open in push-to-talk mode.
+    pub fn set_talking(&self, talking: bool) {
+        self.shared.push_to_talk.store(talking, Ordering::Relaxed);
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.shared.open.load(Ordering::Relaxed)
+    }
+
+    pub fn receiver(&self) -> MicReceiver {
+        let (producer, consumer) = RingBuffer::new(RECEIVER_CAPACITY * 2);
+        self.shared.producers.lock().unwrap().push(producer);
+        MicReceiver {
+            consumer,
+            sample_rate: self.shared.sample_rate,
+            resampler: Resampler::new(),
+            scratch: Vec::new(),
+        }
+    }
+}
+
+fn build_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig, shared: Arc<Shared>) -> anyhow::Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    let hang_frames = (VOICE_HANG_SECONDS * shared.sample_rate as f32) as usize;
+    let mut frames_since_voice = usize::MAX;
+    let mut stereo = Vec::new();
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            stereo.clear();
+            for frame in data.chunks_exact(channels) {
+                let left = frame[0].to_sample::<f32>();
+                let right = if channels > 1 { frame[1].to_sample::<f32>() } else { left };
+                stereo.push(left);
+                stereo.push(right);
+            }
+            let open = if shared.voice_activation.load(Ordering::Relaxed) {
+                let peak = stereo.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+                let threshold_db = f32::from_bits(shared.threshold_db.load(Ordering::Relaxed));
+                if to_decibels(peak) >= threshold_db {
+                    frames_since_voice = 0;
+                } else {
+                    frames_since_voice = frames_since_voice.saturating_add(stereo.len() / 2);
+                }
+                frames_since_voice < hang_frames
+            } else {
+                shared.push_to_talk.load(Ordering::Relaxed)
+            };
+            shared.open.store(open, Ordering::Relaxed);
+            if !open {
+                return;
+            }
+            // Never wait on the audio thread, a receiver being added can miss a block
+            if let Ok(mut producers) = shared.producers.try_lock() {
+                producers.retain(|producer| !producer.is_abandoned());
+                for producer in producers.iter_mut() {
+                    if let Ok(chunk) = producer.write_chunk_uninit(stereo.len()) {
+                        chunk.fill_from_iter(stereo.iter().copied());
+                    }
+                }
+            }
+        },
+        |err| println!("Microphone stream error: {}", err),
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// One encoder's share of the microphone, resampled to 48 kHz.
+pub struct MicReceiver {
+    consumer: Consumer<f32>,
+    sample_rate: u32,
+    resampler: Resampler,
+    scratch: Vec<f32>,
+}
+
+impl AudioSource for MicReceiver {
+    fn read(&mut self, output: &mut Vec<f32>) -> anyhow::Result<()> {
+        self.scratch.clear();
+        while let Ok(sample) = self.consumer.pop() {
+            self.scratch.push(sample);
+        }
+        self.resampler.process(self.sample_rate, &self.scratch, output);
+        Ok(())
+    }
+}
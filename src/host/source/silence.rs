@@ -0,0 +1,21 @@
+use super::{AudioSource, Pacer};
+
+/// Digital silence, useful to keep clients connected while there's nothing to play.
+#[derive(Default)]
+pub struct SilenceSource {
+    pacer: Pacer,
+}
+
+impl SilenceSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AudioSource for SilenceSource {
+    fn read(&mut self, output: &mut Vec<f32>) -> anyhow::Result<()> {
+        let frames = self.pacer.frames_due();
+        output.resize(output.len() + frames * 2, 0.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use super::{AudioSource, Pacer};
+use crate::host::resample::OUTPUT_SAMPLE_RATE;
+use std::f64::consts::TAU;
+
+/// A test tone on both channels.
+pub struct SineSource {
+    frequency: f64,
+    amplitude: f32,
+    phase: f64,
+    pacer: Pacer,
+}
+
+impl SineSource {
+    pub fn new(frequency: f64, amplitude: f32) -> Self {
+        Self {
+            frequency,
+            amplitude,
+            phase: 0.0,
+            pacer: Pacer::new(),
+        }
+    }
+}
+
+impl AudioSource for SineSource {
+    fn read(&mut self, output: &mut Vec<f32>) -> anyhow::Result<()> {
+        let step = self.frequency / OUTPUT_SAMPLE_RATE as f64;
+        for _ in 0..self.pacer.frames_due() {
+            let sample = (self.phase * TAU).sin() as f32 * self.amplitude;
+            output.push(sample);
+            output.push(sample);
+            self.phase = (self.phase + step).fract();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use super::resample::{Resampler, OUTPUT_SAMPLE_RATE};
+use super::source::AudioSource;
+
+use kira::effect::{Effect, EffectBuilder};
+use kira::info::Info;
+use kira::Frame;
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Creates a kira effect that copies the audio of the track it is added to into a ring buffer,
+/// and the receiving end that reads it back as 48 kHz interleaved stereo samples.
+pub fn tap(capacity: usize) -> (TapBuilder, TapReceiver) {
+    let (producer, consumer) = RingBuffer::new(capacity * 2);
+    let sample_rate = Arc::new(AtomicU32::new(OUTPUT_SAMPLE_RATE));
+    (
+        TapBuilder {
+            producer,
+            sample_rate: sample_rate.clone(),
+        },
+        TapReceiver {
+            consumer: Arc::new(Mutex::new(consumer)),
+            sample_rate,
+            resampler: Resampler::new(),
+            scratch: Vec::new(),
+        },
+    )
+}
+
+pub struct TapBuilder {
+    producer: Producer<f32>,
+    sample_rate: Arc<AtomicU32>,
+}
+
+impl EffectBuilder for TapBuilder {
+    type Handle = ();
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        (
+            Box::new(Tap {
+                producer: self.producer,
+                sample_rate: self.sample_rate,
+            }),
+            (),
+        )
+    }
+}
+
+struct Tap {
+    producer: Producer<f32>,
+    sample_rate: Arc<AtomicU32>,
+}
+
+impl Effect for Tap {
+    fn init(&mut self, sample_rate: u32, _internal_buffer_size: usize) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    fn process(&mut self, input: &mut [Frame], _dt: f64, _info: &Info) {
+        for frame in input.iter() {
+            // Drop audio rather than block the audio thread when nobody is reading
+            if self.producer.slots() < 2 {
+                return;
+            }
+            let _ = self.producer.push(frame.left);
+            let _ = self.producer.push(frame.right);
+        }
+    }
+}
+
+pub struct TapReceiver {
+    // Shared with the receivers reopened from this one, only the latest of which is read
+    consumer: Arc<Mutex<Consumer<f32>>>,
+    sample_rate: Arc<AtomicU32>,
+    resampler: Resampler,
+    scratch: Vec<f32>,
+}
+
+impl TapReceiver {
+    /// Another receiver for the same tap, starting from a clean resampler, for when this one can't be trusted any more.
+    pub fn reopen(&self) -> Self {
+        Self {
+            consumer: self.consumer.clone(),
+            sample_rate: self.sample_rate.clone(),
+            resampler: Resampler::new(),
+            scratch: Vec::new(),
+        }
+    }
+}
+
+impl AudioSource for TapReceiver {
+    fn read(&mut self, output: &mut Vec<f32>) -> anyhow::Result<()> {
+        self.scratch.clear();
+        // A receiver that panicked mid-read leaves the ring buffer itself intact
+        let mut consumer = self.consumer.lock().unwrap_or_else(PoisonError::into_inner);
+        while let Ok(sample) = consumer.pop() {
+            self.scratch.push(sample);
+        }
+        drop(consumer);
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed);
+        self.resampler.process(sample_rate, &self.scratch, output);
+        Ok(())
+    }
+}
@@ -1,101 +1,224 @@
+use std::io;
 use std::io::ErrorKind;
-use crate::host::playlist::Track;
-use iced::alignment::Horizontal;
-use iced::widget::{button, column, container, row, scrollable, slider, text, Column, Container};
-use iced::{Element, Fill};
-use iced::Length;
-use kira::sound::static_sound::StaticSoundData;
-use crate::host::host::{Error, Message};
-use crate::host::host::Error::IoError;
+use crate::host::effects::Effects;
+use crate::host::loudness;
+use crate::host::sound::SoundData;
+use crate::host::playlist::{Cue, Fade, Playlist, Scene, Track};
+use kira::sound::{EndPosition, PlaybackPosition, Region};
+use kira::Tween;
+use crate::host::track::Error::IoError;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    DialogClosed,
+    IoError(io::ErrorKind),
+}
 
 #[derive(Debug, Clone)]
 pub enum MultiplayerTrackMessage {
     Play(bool),
+    /// Starts the track looping on a layer of its own over the current track, or fades that layer out.
+    ToggleLayer,
+    ToggleSfx,
+    EffectsChanged(Effects),
     UpdateVolumeSlider(f64),
     Remove,
     MoveTrackUp,
     MoveTrackDown,
+    ToggleEditor,
+    LoopStartChanged(f64),
+    LoopEndChanged(f64),
+    /// Moves the loop start to where the track is playing.
+    SetLoopStartHere,
+    SetLoopEndHere,
+    ClearLoop,
+    CueNameChanged(String),
+    /// Adds a cue where the track is playing, or at its start if it isn't.
+    AddCue,
+    JumpToCue(usize),
+    RemoveCue(usize),
+    StartOffsetChanged(f64),
+    TrimEndChanged(f64),
+    FadeInChanged(Option<Fade>),
+    FadeOutChanged(Option<Fade>),
 }
 
+// Shortest loop or trimmed track the editor allows, in seconds
+const MIN_LOOP_LENGTH: f64 = 0.05;
+
 
 #[derive(Debug, Clone)]
 pub struct MultiplayerTrack {
     pub path: String,
-    pub data: StaticSoundData,
+    pub data: SoundData,
     pub volume: f64,
+    pub loop_start: Option<f64>,
+    pub loop_end: Option<f64>,
+    pub cues: Vec<Cue>,
+    pub start_offset: f64,
+    pub trim_end: Option<f64>,
+    pub fade_in: Option<Fade>,
+    pub fade_out: Option<Fade>,
+    pub sfx: bool,
+    pub effects: Effects,
+    pub loudness: Option<f64>,
+    pub peak: Option<f64>,
+    /// Whether the loudness is still being measured in the background.
+    pub analyzing: bool,
+    pub editing: bool,
+    pub new_cue_name: String,
 }
 
 impl MultiplayerTrack {
     pub fn new(path: String) -> Result<Self, Error> {
-        let static_sound_data = StaticSoundData::from_file(path.clone());
-        match static_sound_data {
+        let sound_data = SoundData::from_file(&path);
+        match sound_data {
             Ok(data) => Ok(Self {
                 path,
                 data,
                 volume: 1.0,
+                loop_start: None,
+                loop_end: None,
+                cues: Vec::new(),
+                start_offset: 0.0,
+                trim_end: None,
+                fade_in: None,
+                fade_out: None,
+                sfx: false,
+                effects: Effects::default(),
+                loudness: None,
+                peak: None,
+                analyzing: false,
+                editing: false,
+                new_cue_name: String::new(),
             }),
             Err(_) => Err(IoError(ErrorKind::InvalidData)),
         }
     }
     
     pub fn from(track: &Track) -> Result<Self, Error> {
-        let static_sound_data = StaticSoundData::from_file(track.path.clone());
-        match static_sound_data {
+        let sound_data = SoundData::from_file(&track.path);
+        match sound_data {
             Ok(data) => Ok(Self {
                 path: track.path.clone(),
                 data,
                 volume: track.volume,
+                loop_start: track.loop_start,
+                loop_end: track.loop_end,
+                cues: track.cues.clone(),
+                start_offset: track.start_offset,
+                trim_end: track.trim_end,
+                fade_in: track.fade_in,
+                fade_out: track.fade_out,
+                sfx: track.sfx,
+                effects: track.effects,
+                loudness: track.loudness,
+                peak: track.peak,
+                analyzing: false,
+                editing: false,
+                new_cue_name: String::new(),
             }),
             Err(_) => Err(IoError(ErrorKind::InvalidData)),
         }
     }
-    
-    pub fn view(&self, currently_playing: bool) -> Element<MultiplayerTrackMessage> {
-        let audio_slider: Container<MultiplayerTrackMessage> = container(
-            slider(
-                0.0..=1.0,
-                self.volume,
-                MultiplayerTrackMessage::UpdateVolumeSlider,
-            )
-                .height(16)
-                .step(0.01)
-                .width(Fill)
-        )
-            .center_x(Fill)
-            .padding([10, 40]);
-
-        let top_row: Container<MultiplayerTrackMessage> = container(
-            row![
-                container(
-                    button("Play").on_press(MultiplayerTrackMessage::Play(false)).height(32)
-                ).align_x(Horizontal::Left)
-                .padding([2, 4]),
-                container(
-                    button("Reset").on_press(MultiplayerTrackMessage::Play(true)).height(32)
-                ).align_x(Horizontal::Left)
-                .padding([2, 4]),
-                text(self.path.to_string()).align_x(Horizontal::Center).width(Fill),
-                column![
-                    button("Remove").on_press(MultiplayerTrackMessage::Remove).height(32),
-                ].align_x(Horizontal::Right),
-                column![
-                    button("UP").on_press(MultiplayerTrackMessage::MoveTrackUp).height(16),
-                    button("DOWN").on_press(MultiplayerTrackMessage::MoveTrackDown).height(16),
-                ].align_x(Horizontal::Right),
-            ]
-                .spacing(4)
-        )
-            .center_x(Fill)
-            .width(Fill)
-            .padding([2, 20]);
-
-        container(
-            column![
-                        top_row,
-                        audio_slider,
-                ]
-        ).style(if currently_playing {container::rounded_box} else {container::dark})
-            .into()
+
+    pub fn to_track(&self) -> Track {
+        Track {
+            path: self.path.clone(),
+            volume: self.volume,
+            loop_start: self.loop_start,
+            loop_end: self.loop_end,
+            cues: self.cues.clone(),
+            start_offset: self.start_offset,
+            trim_end: self.trim_end,
+            fade_in: self.fade_in,
+            fade_out: self.fade_out,
+            sfx: self.sfx,
+            effects: self.effects,
+            loudness: self.loudness,
+            peak: self.peak,
+        }
+    }
+
+    /// The gain in dB that brings the track to `target` LUFS, none if there's no target or it hasn't been measured.
+    /// Without a peak, as in playlists saved before peaks were measured, it's treated as full scale until it is.
+    pub fn normalization_gain(&self, target: Option<f64>) -> f64 {
+        match (self.loudness, target) {
+            (Some(loudness), Some(target)) => loudness::normalization_gain(loudness, self.peak.unwrap_or(0.0), target),
+            _ => 0.0,
+        }
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.data.duration().as_secs_f64()
+    }
+
+    /// Where playback stops, after trimming.
+    pub fn end(&self) -> f64 {
+        self.trim_end.unwrap_or(self.duration())
+    }
+
+    /// The trimmed track unless loop points are set.
+    pub fn loop_region(&self) -> Region {
+        let end = self.loop_end.or(self.trim_end);
+        Region {
+            start: PlaybackPosition::Seconds(self.loop_start.unwrap_or(self.start_offset)),
+            end: end.map_or(EndPosition::EndOfAudio, |end| EndPosition::Custom(PlaybackPosition::Seconds(end))),
+        }
+    }
+
+    /// Keeps the loop start before the loop end.
+    pub fn set_loop_start(&mut self, start: f64) {
+        let end = self.loop_end.unwrap_or(self.end());
+        self.loop_start = Some(start.clamp(0.0, (end - MIN_LOOP_LENGTH).max(0.0)));
+    }
+
+    pub fn set_loop_end(&mut self, end: f64) {
+        let duration = self.duration();
+        let start = self.loop_start.unwrap_or(self.start_offset);
+        self.loop_end = Some(end.clamp((start + MIN_LOOP_LENGTH).min(duration), duration));
+    }
+
+    /// Keeps the start offset before the trimmed end.
+    pub fn set_start_offset(&mut self, start: f64) {
+        self.start_offset = start.clamp(0.0, (self.end() - MIN_LOOP_LENGTH).max(0.0));
+    }
+
+    pub fn set_trim_end(&mut self, end: f64) {
+        let duration = self.duration();
+        self.trim_end = Some(end.clamp((self.start_offset + MIN_LOOP_LENGTH).min(duration), duration));
+    }
+
+    /// Where playing the track picks up from `position`, which is only kept if it's within the trimmed track.
+    pub fn resume_position(&self, position: f64) -> f64 {
+        if (self.start_offset..self.end()).contains(&position) {
+            position
+        } else {
+            self.start_offset
+        }
+    }
+
+    /// This track's fade in if it has one, otherwise `default`.
+    pub fn fade_in_tween(&self, default: Tween) -> Tween {
+        self.fade_in.map_or(default, |fade| fade.tween(default.start_time))
+    }
+
+    pub fn fade_out_tween(&self, default: Tween) -> Tween {
+        self.fade_out.map_or(default, |fade| fade.tween(default.start_time))
+    }
+
+    /// Named after `new_cue_name`, or numbered if that's empty.
+    pub fn add_cue(&mut self, position: f64) {
+        let name = match self.new_cue_name.trim() {
+            "" => format!("Cue {}", self.cues.len() + 1),
+            name => name.to_string(),
+        };
+        self.cues.push(Cue {
+            name,
+            position,
+        });
+        self.cues.sort_by(|a, b| a.position.total_cmp(&b.position));
+        self.new_cue_name.clear();
     }
 }
 
@@ -104,9 +227,12 @@ pub enum MultiplayerPlaylistMessage {
     MultiplayerTrack(usize, MultiplayerTrackMessage),
 }
 
+#[derive(Debug, Clone)]
 pub struct MultiplayerPlaylist {
     pub tracks: Vec<MultiplayerTrack>,
     pub current_track: Option<usize>,
+    pub scenes: Vec<Scene>,
+    pub master_effects: Effects,
 }
 
 impl Default for MultiplayerPlaylist {
@@ -120,8 +246,22 @@ impl MultiplayerPlaylist {
         Self {
             tracks: Vec::new(),
             current_track: None,
+            scenes: Vec::new(),
+            master_effects: Effects::default(),
         }
     }
+
+    /// Loads every track of an imported playlist.
+    pub fn from(playlist: &Playlist) -> Result<Self, Error> {
+        Ok(Self {
+            tracks: playlist.tracks.iter()
+                .map(MultiplayerTrack::from)
+                .collect::<Result<Vec<MultiplayerTrack>, Error>>()?,
+            current_track: None,
+            scenes: playlist.scenes.clone(),
+            master_effects: playlist.master_effects,
+        })
+    }
     
     pub fn add_track(&mut self, track: MultiplayerTrack) {
         self.tracks.push(track);
@@ -149,27 +289,12 @@ impl MultiplayerPlaylist {
     pub fn get_current_track(&self) -> Option<&MultiplayerTrack> {
         self.current_track.and_then(|index| self.tracks.get(index))
     }
-    
-    pub fn view(&self) -> Element<'_, Message> {
-        let multiplayer_track_views: Vec<Element<MultiplayerPlaylistMessage>> = self.tracks.iter()
-            .enumerate()
-            .map(|index| MultiplayerTrack::view(index.1, self.current_track.is_some_and(|_| index.0 == self.current_track.unwrap())))
-            .enumerate()
-            .map(|(index, track)| {
-                track.map(move |message| MultiplayerPlaylistMessage::MultiplayerTrack(index, message))
-            })
-            .collect();
-        
-        let container: Element<'_, MultiplayerPlaylistMessage> = Container::new(
-            scrollable(
-                Column::with_children(multiplayer_track_views)
-            )
-        )
-            .height(Length::FillPortion(3))
-            .padding(10)
-            .center_x(Fill)
-            .into();
-        container.map(Message::MultiplayerPlaylist)
+
+    pub fn to_playlist(&self) -> Playlist {
+        Playlist {
+            tracks: self.tracks.iter().map(MultiplayerTrack::to_track).collect(),
+            scenes: self.scenes.clone(),
+            master_effects: self.master_effects,
+        }
     }
 }
-
@@ -0,0 +1,7 @@
+pub mod client;
+pub mod host;
+pub mod lossless;
+pub mod meter;
+pub mod multistream;
+pub mod protocol;
+pub mod settings;
@@ -0,0 +1,44 @@
+pub mod flac;
+
+use crate::multistream::SAMPLE_RATE;
+use crate::protocol::{ChannelLayout, Codec, StreamParameters};
+
+/// Both lossless codecs carry 16-bit samples, CD quality at 48 kHz.
+pub const BITS_PER_SAMPLE: u32 = 16;
+const FULL_SCALE: f32 = i16::MAX as f32;
+
+pub fn parameters(codec: Codec, layout: ChannelLayout, frame_size: usize) -> StreamParameters {
+    StreamParameters {
+        codec,
+        sample_rate: SAMPLE_RATE,
+        layout,
+        streams: 0,
+        coupled_streams: 0,
+        mapping: Vec::new(),
+        frame_size,
+    }
+}
+
+pub fn quantize(samples: &[f32]) -> Vec<i32> {
+    samples.iter()
+        .map(|sample| (sample.clamp(-1.0, 1.0) * FULL_SCALE).round() as i32)
+        .collect()
+}
+
+pub fn encode_pcm(samples: &[f32]) -> Vec<u8> {
+    quantize(samples)
+        .into_iter()
+        .flat_map(|sample| (sample as i16).to_le_bytes())
+        .collect()
+}
+
+pub fn decode_pcm(packet: &[u8], output: &mut Vec<f32>) -> anyhow::Result<()> {
+    if !packet.len().is_multiple_of(2) {
+        anyhow::bail!("PCM packet of {} bytes splits a sample", packet.len());
+    }
+    output.extend(
+        packet.chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / FULL_SCALE)
+    );
+    Ok(())
+}
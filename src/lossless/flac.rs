@@ -0,0 +1,419 @@
+use super::{quantize, BITS_PER_SAMPLE, FULL_SCALE};
+
+use claxon::frame::FrameReader;
+use std::io::Cursor;
+
+// Sync code, reserved bit and fixed block size
+const FRAME_SYNC: u64 = 0xFFF8;
+// Block size is stored as a 16-bit value after the frame number
+const BLOCK_SIZE_16_BIT: u64 = 0b0111;
+const SAMPLE_RATE_48000: u64 = 0b1010;
+const SAMPLE_SIZE_16_BIT: u64 = 0b100;
+const MAX_FIXED_ORDER: usize = 4;
+// Rice parameter 15 escapes to raw samples, so 14 is the largest real one
+const MAX_RICE_PARAMETER: u32 = 14;
+// Up to 64 partitions, each with its own Rice parameter, as the reference encoder's default levels search
+const MAX_PARTITION_ORDER: u32 = 6;
+const RICE_PARAMETER_BITS: u64 = 4;
+
+/// Writes one FLAC frame per packet: independent channels, each a constant, verbatim or fixed-predictor subframe
+/// with a partitioned Rice residual. No stream header is sent, the stream parameters carry what it would.
+#[derive(Debug, Default)]
+pub struct FlacEncoder {
+    frame_number: u32,
+}
+
+impl FlacEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encodes interleaved 48 kHz samples as the next frame.
+    pub fn encode(&mut self, samples: &[f32], channels: usize) -> Vec<u8> {
+        let samples = quantize(samples);
+        let block_size = samples.len() / channels;
+        let mut writer = BitWriter::new();
+
+        writer.write(FRAME_SYNC, 16);
+        writer.write(BLOCK_SIZE_16_BIT, 4);
+        writer.write(SAMPLE_RATE_48000, 4);
+        writer.write(channels as u64 - 1, 4);
+        writer.write(SAMPLE_SIZE_16_BIT, 3);
+        writer.write(0, 1);
+        write_utf8(&mut writer, self.frame_number);
+        writer.write(block_size as u64 - 1, 16);
+        let crc = crc8(&writer.bytes);
+        writer.write(crc as u64, 8);
+
+        for channel in 0..channels {
+            let channel_samples = samples.iter()
+                .skip(channel)
+                .step_by(channels)
+                .map(|sample| *sample as i64)
+                .collect::<Vec<i64>>();
+            write_subframe(&mut writer, &channel_samples);
+        }
+        writer.align();
+        let crc = crc16(&writer.bytes);
+        writer.write(crc as u64, 16);
+
+        // Frame numbers are 31 bits
+        self.frame_number = (self.frame_number + 1) & 0x7FFF_FFFF;
+        writer.bytes
+    }
+}
+
+fn write_subframe(writer: &mut BitWriter, samples: &[i64]) {
+    let bits = BITS_PER_SAMPLE;
+    if samples.iter().all(|sample| *sample == samples[0]) {
+        writer.write(0, 8);
+        writer.write_signed(samples[0], bits);
+        return;
+    }
+
+    let verbatim_bits = samples.len() as u64 * bits as u64;
+    let best = (0..=MAX_FIXED_ORDER.min(samples.len() - 1))
+        .map(|order| {
+            let residual = (order..samples.len())
+                .map(|index| zigzag(samples[index] - fixed_prediction(samples, index, order)))
+                .collect::<Vec<u64>>();
+            let partitions = rice_partitions(&residual, samples.len(), order);
+            // Warm-up samples, then coding method and partition order ahead of the partitions
+            let total_bits = order as u64 * bits as u64 + 6 + partitions.bits;
+            (order, partitions, residual, total_bits)
+        })
+        .min_by_key(|(_, _, _, total_bits)| *total_bits);
+
+    match best {
+        Some((order, partitions, residual, total_bits)) if total_bits < verbatim_bits => {
+            writer.write(0b0001_0000 | ((order as u64) << 1), 8);
+            for sample in &samples[..order] {
+                writer.write_signed(*sample, bits);
+            }
+            writer.write(0, 2);
+            writer.write(partitions.order as u64, 4);
+            let mut residual = residual.as_slice();
+            for (index, parameter) in partitions.parameters.iter().enumerate() {
+                let (partition, rest) = residual.split_at(partition_length(samples.len(), partitions.order, order, index));
+                writer.write(*parameter as u64, RICE_PARAMETER_BITS as u32);
+                for value in partition {
+                    writer.write_unary(value >> parameter);
+                    writer.write(*value, *parameter);
+                }
+                residual = rest;
+            }
+        }
+        _ => {
+            writer.write(0b0000_0010, 8);
+            for sample in samples {
+                writer.write_signed(*sample, bits);
+            }
+        }
+    }
+}
+
+/// How a residual is split up for Rice coding.
+struct RicePartitions {
+    order: u32,
+    parameters: Vec<u32>,
+    /// Bits the partitions take, parameters included.
+    bits: u64,
+}
+
+/// The partition order and Rice parameters that code `residual` in the fewest bits. The first partition is
+/// short by the `predictor_order` warm-up samples, so it has to hold at least that many.
+fn rice_partitions(residual: &[u64], block_size: usize, predictor_order: usize) -> RicePartitions {
+    (0..=MAX_PARTITION_ORDER)
+        .take_while(|order| block_size.is_multiple_of(1 << order) && block_size >> order >= predictor_order)
+        .map(|order| {
+            let mut remaining = residual;
+            let mut parameters = Vec::with_capacity(1 << order);
+            let mut bits = 0;
+            for index in 0..1 << order {
+                let (partition, rest) = remaining.split_at(partition_length(block_size, order, predictor_order, index));
+                let (parameter, partition_bits) = rice_parameter(partition);
+                parameters.push(parameter);
+                bits += RICE_PARAMETER_BITS + partition_bits;
+                remaining = rest;
+            }
+            RicePartitions {
+                order,
+                parameters,
+                bits,
+            }
+        })
+        .min_by_key(|partitions| partitions.bits)
+        .unwrap()
+}
+
+fn partition_length(block_size: usize, partition_order: u32, predictor_order: usize, index: usize) -> usize {
+    let length = block_size >> partition_order;
+    if index == 0 { length - predictor_order } else { length }
+}
+
+/// The Rice parameter that codes `residual` in the fewest bits, and that many bits.
+fn rice_parameter(residual: &[u64]) -> (u32, u64) {
+    (0..=MAX_RICE_PARAMETER)
+        .map(|parameter| {
+            let bits = residual.iter().map(|value| (value >> parameter) + 1 + parameter as u64).sum::<u64>();
+            (parameter, bits)
+        })
+        .min_by_key(|(_, bits)| *bits)
+        .unwrap()
+}
+
+fn fixed_prediction(samples: &[i64], index: usize, order: usize) -> i64 {
+    match order {
+        0 => 0,
+        1 => samples[index - 1],
+        2 => 2 * samples[index - 1] - samples[index - 2],
+        3 => 3 * samples[index - 1] - 3 * samples[index - 2] + samples[index - 3],
+        _ => 4 * samples[index - 1] - 6 * samples[index - 2] + 4 * samples[index - 3] - samples[index - 4],
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Frame numbers use the same variable length coding as UTF-8, extended to 31 bits.
+fn write_utf8(writer: &mut BitWriter, value: u32) {
+    if value < 0x80 {
+        writer.write(value as u64, 8);
+        return;
+    }
+    let length = match value {
+        0..0x800 => 2,
+        0x800..0x10000 => 3,
+        0x10000..0x200000 => 4,
+        0x200000..0x4000000 => 5,
+        _ => 6,
+    };
+    let prefix = (0xFF00u64 >> length) & 0xFF;
+    writer.write(prefix | (value as u64 >> (6 * (length - 1))), 8);
+    for index in (0..length - 1).rev() {
+        writer.write(0x80 | ((value as u64 >> (6 * index)) & 0x3F), 8);
+    }
+}
+
+/// Decodes one FLAC frame into interleaved samples appended to `output`. The frame is read by claxon,
+/// so anything a FLAC encoder writes decodes, not only what the host's encoder does.
+pub fn decode(packet: &[u8], channels: usize, output: &mut Vec<f32>) -> anyhow::Result<()> {
+    let block = FrameReader::new(Cursor::new(packet))
+        .read_next_or_eof(Vec::new())
+        .map_err(|e| anyhow::anyhow!("Invalid FLAC frame: {}", e))?
+        .ok_or_else(|| anyhow::anyhow!("Empty FLAC packet"))?;
+    if block.channels() as usize != channels {
+        anyhow::bail!("FLAC frame has {} channels, expected {}", block.channels(), channels);
+    }
+    output.reserve(block.duration() as usize * channels);
+    for index in 0..block.duration() {
+        output.extend((0..channels as u32).map(|channel| block.sample(channel, index) as f32 / FULL_SCALE));
+    }
+    Ok(())
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    accumulator: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            accumulator: 0,
+            bits: 0,
+        }
+    }
+
+    /// Writes the low `count` bits of `value`, at most 32.
+    fn write(&mut self, value: u64, count: u32) {
+        if count == 0 {
+            return;
+        }
+        self.accumulator = (self.accumulator << count) | (value & ((1 << count) - 1));
+        self.bits += count;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.bytes.push((self.accumulator >> self.bits) as u8);
+        }
+        self.accumulator &= (1 << self.bits) - 1;
+    }
+
+    fn write_signed(&mut self, value: i64, count: u32) {
+        self.write(value as u64, count);
+    }
+
+    /// `value` zeros and a one.
+    fn write_unary(&mut self, mut value: u64) {
+        while value >= 32 {
+            self.write(0, 32);
+            value -= 32;
+        }
+        self.write(1, value as u32 + 1);
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, byte| {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic noise, so the frames don't all fall to one predictor
+    fn test_signal(frames: usize, channels: usize) -> Vec<f32> {
+        let mut state = 0x1234_5678u32;
+        (0..frames * channels)
+            .map(|index| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = (state >> 16) as f32 / 65536.0 - 0.5;
+                let frame = (index / channels) as f32;
+                0.6 * (frame * 0.05 * (1 + index % channels) as f32).sin() + 0.05 * noise
+            })
+            .collect()
+    }
+
+    fn assert_lossless(input: &[f32], decoded: &[f32]) {
+        let decoded = decoded.iter()
+            .map(|sample| (sample * FULL_SCALE).round() as i32)
+            .collect::<Vec<i32>>();
+        assert_eq!(quantize(input), decoded);
+    }
+
+    #[test]
+    fn stereo_round_trip() {
+        let input = test_signal(480, 2);
+        let packet = FlacEncoder::new().encode(&input, 2);
+        let mut output = Vec::new();
+        decode(&packet, 2, &mut output).unwrap();
+        assert_lossless(&input, &output);
+        assert!(packet.len() < input.len() * 2, "{} bytes is no smaller than PCM", packet.len());
+    }
+
+    #[test]
+    fn silence_uses_constant_subframes() {
+        let input = vec![0.0; 960 * 6];
+        let packet = FlacEncoder::new().encode(&input, 6);
+        assert!(packet.len() < 32, "{} bytes for silence", packet.len());
+        let mut output = Vec::new();
+        decode(&packet, 6, &mut output).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn long_streams_keep_decoding() {
+        // Past 127 frames the frame number takes more than one byte
+        let mut encoder = FlacEncoder::new();
+        let input = test_signal(120, 8);
+        for _ in 0..300 {
+            let packet = encoder.encode(&input, 8);
+            let mut output = Vec::new();
+            decode(&packet, 8, &mut output).unwrap();
+            assert_lossless(&input, &output);
+        }
+    }
+
+    #[test]
+    fn frame_numbers() {
+        // claxon reads the frame number back as the time of the block's first sample
+        for value in [0, 0x7F, 0x80, 0x7FF, 0x800, 0xFFFF, 0x10000, 0x1F_FFFF, 0x20_0000, 0x3FF_FFFF, 0x7FFF_FFFF] {
+            let packet = FlacEncoder { frame_number: value }.encode(&test_signal(120, 2), 2);
+            let block = FrameReader::new(Cursor::new(packet)).read_next_or_eof(Vec::new()).unwrap().unwrap();
+            assert_eq!(block.time(), value as u64 * 120);
+        }
+    }
+
+    #[test]
+    fn residual_is_partitioned_when_the_level_changes() {
+        // A quiet first half and a loud second half want different Rice parameters
+        let input = test_signal(960, 1)
+            .into_iter()
+            .enumerate()
+            .map(|(index, sample)| if index < 480 { sample * 0.01 } else { sample })
+            .collect::<Vec<f32>>();
+        let samples = quantize(&input).into_iter().map(i64::from).collect::<Vec<i64>>();
+        let residual = (1..samples.len())
+            .map(|index| zigzag(samples[index] - fixed_prediction(&samples, index, 1)))
+            .collect::<Vec<u64>>();
+        let partitions = rice_partitions(&residual, samples.len(), 1);
+        assert!(partitions.order > 0);
+        assert!(partitions.bits < rice_parameter(&residual).1 + RICE_PARAMETER_BITS);
+
+        let packet = FlacEncoder::new().encode(&input, 1);
+        let mut output = Vec::new();
+        decode(&packet, 1, &mut output).unwrap();
+        assert_lossless(&input, &output);
+    }
+
+    #[test]
+    fn lpc_subframes_decode() {
+        // A frame like another encoder would write, with an order 1 LPC subframe predicting each sample as the last
+        let samples = (0..16).map(|index| index * 300 - 2000).collect::<Vec<i64>>();
+        let mut writer = BitWriter::new();
+        writer.write(FRAME_SYNC, 16);
+        writer.write(BLOCK_SIZE_16_BIT, 4);
+        writer.write(SAMPLE_RATE_48000, 4);
+        writer.write(0, 4);
+        writer.write(SAMPLE_SIZE_16_BIT, 3);
+        writer.write(0, 1);
+        write_utf8(&mut writer, 0);
+        writer.write(samples.len() as u64 - 1, 16);
+        let crc = crc8(&writer.bytes);
+        writer.write(crc as u64, 8);
+        writer.write(0b0100_0000, 8);
+        writer.write_signed(samples[0], BITS_PER_SAMPLE);
+        // Coefficient precision of 15 bits, no shift, and the one coefficient
+        writer.write(14, 4);
+        writer.write(0, 5);
+        writer.write_signed(1, 15);
+        writer.write(0, 2);
+        writer.write(0, 4);
+        writer.write(9, 4);
+        for index in 1..samples.len() {
+            let value = zigzag(samples[index] - samples[index - 1]);
+            writer.write_unary(value >> 9);
+            writer.write(value, 9);
+        }
+        writer.align();
+        let crc = crc16(&writer.bytes);
+        writer.write(crc as u64, 16);
+
+        let mut output = Vec::new();
+        decode(&writer.bytes, 1, &mut output).unwrap();
+        let decoded = output.iter().map(|sample| (sample * FULL_SCALE).round() as i64).collect::<Vec<i64>>();
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn corruption_is_detected() {
+        let mut packet = FlacEncoder::new().encode(&test_signal(480, 2), 2);
+        packet[20] ^= 0x10;
+        assert!(decode(&packet, 2, &mut Vec::new()).is_err());
+    }
+}
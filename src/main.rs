@@ -1,10 +1,14 @@
 use iced::widget::{column, Space};
-use iced::{Element, Font, Length, Subscription, Task, Theme};
+use iced::{window, Element, Font, Length, Subscription, Task, Theme};
 use iced_aw::{TabBarPosition, TabLabel, Tabs};
+use std::time::Duration;
 
-mod client;
-mod host;
-pub mod settings;
+use multiplayer::settings;
+
+mod gui;
+
+// How long closing the app waits for the server to say goodbye to its clients.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(3);
 
 fn main() -> iced::Result {
     iced::application("Multiplayer", update, view)
@@ -12,6 +16,7 @@ fn main() -> iced::Result {
         .font(include_bytes!("../assets/fonts/icons.ttf").as_slice())
         .default_font(Font::MONOSPACE)
         .subscription(subscription)
+        .exit_on_close_request(false)
         .run_with(State::new)
 }
 
@@ -22,20 +27,21 @@ fn theme(_state: &State) -> Theme {
 
 struct State {
     screen: Screen,
+    exiting: bool,
 }
 
 impl State {
     fn new() -> (Self, Task<Message>) {
         // TODO: Read a config file here and determine which screen to start on
-        let settings: settings::Settings = confy::load("multiplayer", None).unwrap_or_default();
+        let settings = settings::load();
         match settings.mode {
             settings::Mode::Host => {
-                let (host, task) = host::host::Host::new(settings);
-                let state = State { screen: Screen::Host(host) };
+                let (host, task) = gui::host::Host::new(settings);
+                let state = State { screen: Screen::Host(host), exiting: false };
                 (state, task.map(Message::Host))
             },
             settings::Mode::Client => {
-                let state = State { screen: Screen::Client(client::client::Client::new()) };
+                let state = State { screen: Screen::Client(gui::client::Client::new()), exiting: false };
                 (state, iced::widget::focus_next())
             }
         }
@@ -43,15 +49,17 @@ impl State {
 }
 
 enum Screen {
-    Host(host::host::Host),
-    Client(client::client::Client),
+    Host(gui::host::Host),
+    Client(gui::client::Client),
 }
 
 #[derive(Debug, Clone)]
 enum Message {
-    Host(host::host::Message),
-    Client(client::client::Message),
+    Host(gui::host::Message),
+    Client(gui::client::Message),
     TabSelected(TabId),
+    CloseRequested(window::Id),
+    ShutdownTimedOut,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Default)]
@@ -71,17 +79,25 @@ impl TabId {
 }
 
 fn subscription(state: &State) -> Subscription<Message> {
-    match &state.screen {
+    let screen_subscription = match &state.screen {
         Screen::Client(client) => client.subscription().map(Message::Client),
         Screen::Host(host) => host.subscription().map(Message::Host),
-    }
+    };
+    Subscription::batch([
+        screen_subscription,
+        window::close_requests().map(Message::CloseRequested),
+    ])
 }
 
 fn update(state: &mut State, message: Message) -> Task<Message> {
     match message {
         Message::Host(message) => {
             if let Screen::Host(host) = &mut state.screen {
-                host.update(message).map(Message::Host)
+                let task = host.update(message).map(Message::Host);
+                if state.exiting && !host.server_running {
+                    return iced::exit();
+                }
+                task
             } else {
                 Task::none()
             }
@@ -97,11 +113,16 @@ fn update(state: &mut State, message: Message) -> Task<Message> {
             println!("Tab selected: {:?}", tab_id);
             match tab_id {
                 TabId::Host => {
+                    if let Screen::Host(_) = state.screen {
+                        return Task::none();
+                    }
                     println!("Starting host");
-                    let mut settings: settings::Settings = confy::load("multiplayer", None).unwrap_or_default();
+                    let mut settings = settings::load();
                     settings.mode = settings::Mode::Host;
-                    confy::store("multiplayer", None, &settings).unwrap();
-                    let (host, task) = host::host::Host::new(settings);
+                    if let Err(e) = settings::save(&settings) {
+                        println!("Error saving settings: {}", e);
+                    }
+                    let (host, task) = gui::host::Host::new(settings);
                     state.screen = Screen::Host(host);
                     Task::batch([
                         task.map(Message::Host)
@@ -110,29 +131,10 @@ fn update(state: &mut State, message: Message) -> Task<Message> {
                 TabId::Client => {
                     println!("Starting client");
                     if let Screen::Host(host) = &mut state.screen {
-                        settings::save(&host).unwrap();
-                        let task_handle = host.task_handle.take();
-                        println!("Aborting server task");
-                        match task_handle {
-                            Some(task_handle) => task_handle.abort(),
-                            None => {
-                                println!("No task handle to abort");
-                            }
-                        }
-                        match host.capture_thread_handle.take() {
-                            Some(capture_thread_handle) => {
-                                println!("Joining capture thread");
-                                let cancel = host.tx_cancel.take();
-                                cancel.unwrap().send(()).unwrap();
-                                host.rx_capt.take();
-                                capture_thread_handle.join().unwrap();
-
-                            }
-                            None => {
-                                println!("No capture thread handle to join");
-                            }       
-                        }
-                        state.screen = Screen::Client(client::client::Client::new());
+                        host.save_settings();
+                        println!("Ending host session");
+                        host.end_session("Host switched to client mode");
+                        state.screen = Screen::Client(gui::client::Client::new());
                     }
                     Task::batch([
                         iced::widget::focus_next()
@@ -140,6 +142,22 @@ fn update(state: &mut State, message: Message) -> Task<Message> {
                 },
             }
         },
+        Message::CloseRequested(_id) => {
+            match &mut state.screen {
+                Screen::Host(host) if host.server_running => {
+                    host.save_settings();
+                    println!("Ending host session before exit");
+                    host.end_session("Host closed the application");
+                    state.exiting = true;
+                    Task::perform(tokio::time::sleep(SHUTDOWN_GRACE_PERIOD), |_| Message::ShutdownTimedOut)
+                },
+                _ => iced::exit(),
+            }
+        },
+        Message::ShutdownTimedOut => {
+            println!("Server did not shut down in time, exiting anyway");
+            iced::exit()
+        },
     }
 }
 
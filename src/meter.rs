@@ -0,0 +1,63 @@
+/// Per-channel levels of the samples seen since the last `LevelMeter::take`, as linear amplitudes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Levels {
+    pub peak: Vec<f32>,
+    pub rms: Vec<f32>,
+    /// Whether any sample reached full scale.
+    pub clipped: bool,
+}
+
+/// Accumulates peak and RMS levels of interleaved samples until they're taken.
+#[derive(Debug, Default)]
+pub struct LevelMeter {
+    peak: Vec<f32>,
+    sum_squares: Vec<f64>,
+    frames: usize,
+    clipped: bool,
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process(&mut self, samples: &[f32], channels: usize) {
+        if channels == 0 {
+            return;
+        }
+        if self.peak.len() != channels {
+            self.peak = vec![0.0; channels];
+            self.sum_squares = vec![0.0; channels];
+            self.frames = 0;
+        }
+        for frame in samples.chunks_exact(channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                let amplitude = sample.abs();
+                self.peak[channel] = self.peak[channel].max(amplitude);
+                self.sum_squares[channel] += (*sample as f64) * (*sample as f64);
+                self.clipped |= amplitude >= 1.0;
+            }
+            self.frames += 1;
+        }
+    }
+
+    /// Returns the levels since the last call and starts over.
+    pub fn take(&mut self) -> Levels {
+        let frames = self.frames.max(1) as f64;
+        let levels = Levels {
+            peak: self.peak.clone(),
+            rms: self.sum_squares.iter().map(|sum| (sum / frames).sqrt() as f32).collect(),
+            clipped: self.clipped,
+        };
+        self.peak.iter_mut().for_each(|peak| *peak = 0.0);
+        self.sum_squares.iter_mut().for_each(|sum| *sum = 0.0);
+        self.frames = 0;
+        self.clipped = false;
+        levels
+    }
+}
+
+/// Amplitude in dBFS, floored at -96 dB for silence.
+pub fn to_decibels(amplitude: f32) -> f32 {
+    (20.0 * amplitude.max(1e-12).log10()).max(-96.0)
+}
@@ -0,0 +1,420 @@
+use crate::protocol::{ChannelLayout, Codec, StreamParameters};
+
+use audiopus_sys as ffi;
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_int;
+
+pub const SAMPLE_RATE: u32 = 48000;
+// Largest packet libopus produces for one stream, times the streams of a 7.1 layout
+pub const MAX_PACKET_SIZE: usize = 1275 * 5;
+// 120 ms, the longest packet Opus allows
+pub const MAX_FRAME_SIZE: usize = 5760;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Application {
+    #[default]
+    Audio,
+    Voip,
+    LowDelay,
+}
+
+impl Application {
+    pub const ALL: [Application; 3] = [Self::Audio, Self::Voip, Self::LowDelay];
+
+    fn to_ffi(self) -> c_int {
+        match self {
+            Self::Audio => ffi::OPUS_APPLICATION_AUDIO,
+            Self::Voip => ffi::OPUS_APPLICATION_VOIP,
+            Self::LowDelay => ffi::OPUS_APPLICATION_RESTRICTED_LOWDELAY,
+        }
+    }
+}
+
+impl fmt::Display for Application {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Audio => "Music",
+            Self::Voip => "Voice",
+            Self::LowDelay => "Low delay",
+        })
+    }
+}
+
+/// Upper limit of the coded audio bandwidth. `Auto` lets Opus pick from the bitrate.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Bandwidth {
+    #[default]
+    Auto,
+    Narrowband,
+    Mediumband,
+    Wideband,
+    Superwideband,
+    Fullband,
+}
+
+impl Bandwidth {
+    pub const ALL: [Bandwidth; 6] = [
+        Self::Auto, Self::Narrowband, Self::Mediumband, Self::Wideband, Self::Superwideband, Self::Fullband,
+    ];
+
+    fn to_ffi(self) -> c_int {
+        match self {
+            Self::Auto => ffi::OPUS_AUTO,
+            Self::Narrowband => ffi::OPUS_BANDWIDTH_NARROWBAND,
+            Self::Mediumband => ffi::OPUS_BANDWIDTH_MEDIUMBAND,
+            Self::Wideband => ffi::OPUS_BANDWIDTH_WIDEBAND,
+            Self::Superwideband => ffi::OPUS_BANDWIDTH_SUPERWIDEBAND,
+            Self::Fullband => ffi::OPUS_BANDWIDTH_FULLBAND,
+        }
+    }
+}
+
+impl fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "Auto",
+            Self::Narrowband => "4 kHz",
+            Self::Mediumband => "6 kHz",
+            Self::Wideband => "8 kHz",
+            Self::Superwideband => "12 kHz",
+            Self::Fullband => "20 kHz",
+        })
+    }
+}
+
+/// Packet durations Opus can encode in one call.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameDuration {
+    Ms2_5,
+    Ms5,
+    #[default]
+    Ms10,
+    Ms20,
+    Ms40,
+    Ms60,
+}
+
+impl FrameDuration {
+    pub const ALL: [FrameDuration; 6] = [Self::Ms2_5, Self::Ms5, Self::Ms10, Self::Ms20, Self::Ms40, Self::Ms60];
+
+    /// Samples per channel at 48 kHz.
+    pub const fn frame_size(&self) -> usize {
+        match self {
+            Self::Ms2_5 => 120,
+            Self::Ms5 => 240,
+            Self::Ms10 => 480,
+            Self::Ms20 => 960,
+            Self::Ms40 => 1920,
+            Self::Ms60 => 2880,
+        }
+    }
+}
+
+impl fmt::Display for FrameDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ms2_5 => "2.5 ms",
+            Self::Ms5 => "5 ms",
+            Self::Ms10 => "10 ms",
+            Self::Ms20 => "20 ms",
+            Self::Ms40 => "40 ms",
+            Self::Ms60 => "60 ms",
+        })
+    }
+}
+
+fn check(function: &str, code: c_int) -> anyhow::Result<c_int> {
+    if code < 0 {
+        let message = unsafe { CStr::from_ptr(ffi::opus_strerror(code)) };
+        anyhow::bail!("{} failed: {}", function, message.to_string_lossy());
+    }
+    Ok(code)
+}
+
+/// Opus multistream encoder for any `ChannelLayout`. Mono and stereo use mapping family 0,
+/// which produces plain Opus packets.
+pub struct MultistreamEncoder {
+    encoder: *mut ffi::OpusMSEncoder,
+    layout: ChannelLayout,
+    streams: u8,
+    coupled_streams: u8,
+    mapping: Vec<u8>,
+}
+
+// libopus encoder state has no thread affinity, it only must not be used from two threads at once
+unsafe impl Send for MultistreamEncoder {}
+
+impl MultistreamEncoder {
+    pub fn new(layout: ChannelLayout, application: Application) -> anyhow::Result<Self> {
+        let channels = layout.channels();
+        let mapping_family = if channels <= 2 { 0 } else { 1 };
+        let mut streams = 0;
+        let mut coupled_streams = 0;
+        let mut mapping = vec![0u8; channels];
+        let mut error = 0;
+        let encoder = unsafe {
+            ffi::opus_multistream_surround_encoder_create(
+                SAMPLE_RATE as c_int,
+                channels as c_int,
+                mapping_family,
+                &mut streams,
+                &mut coupled_streams,
+                mapping.as_mut_ptr(),
+                application.to_ffi(),
+                &mut error,
+            )
+        };
+        check("opus_multistream_surround_encoder_create", error)?;
+        if encoder.is_null() {
+            anyhow::bail!("opus_multistream_surround_encoder_create returned no encoder");
+        }
+        Ok(Self {
+            encoder,
+            layout,
+            streams: streams as u8,
+            coupled_streams: coupled_streams as u8,
+            mapping,
+        })
+    }
+
+    pub fn layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    pub fn parameters(&self, frame_size: usize) -> StreamParameters {
+        StreamParameters {
+            codec: Codec::Opus,
+            sample_rate: SAMPLE_RATE,
+            layout: self.layout,
+            streams: self.streams,
+            coupled_streams: self.coupled_streams,
+            mapping: self.mapping.clone(),
+            frame_size,
+        }
+    }
+
+    /// Bitrate for all streams together, in bits per second.
+    pub fn set_bitrate(&mut self, bitrate: i32) -> anyhow::Result<()> {
+        self.ctl("OPUS_SET_BITRATE", ffi::OPUS_SET_BITRATE_REQUEST, bitrate)
+    }
+
+    /// 0 is fastest, 10 sounds best.
+    pub fn set_complexity(&mut self, complexity: i32) -> anyhow::Result<()> {
+        self.ctl("OPUS_SET_COMPLEXITY", ffi::OPUS_SET_COMPLEXITY_REQUEST, complexity)
+    }
+
+    /// Variable bitrate when true, constant bitrate otherwise.
+    pub fn set_vbr(&mut self, vbr: bool) -> anyhow::Result<()> {
+        self.ctl("OPUS_SET_VBR", ffi::OPUS_SET_VBR_REQUEST, vbr as c_int)
+    }
+
+    pub fn set_bandwidth(&mut self, bandwidth: Bandwidth) -> anyhow::Result<()> {
+        self.ctl("OPUS_SET_BANDWIDTH", ffi::OPUS_SET_BANDWIDTH_REQUEST, bandwidth.to_ffi())
+    }
+
+    fn ctl(&mut self, name: &str, request: c_int, value: c_int) -> anyhow::Result<()> {
+        let code = unsafe { ffi::opus_multistream_encoder_ctl(self.encoder, request, value) };
+        check(name, code).map(|_| ())
+    }
+
+    /// Encodes one frame of interleaved samples in the encoder's layout.
+    pub fn encode_vec_float(&mut self, input: &[f32], max_size: usize) -> anyhow::Result<Vec<u8>> {
+        let frame_size = input.len() / self.layout.channels();
+        let mut output = vec![0u8; max_size];
+        let len = check("opus_multistream_encode_float", unsafe {
+            ffi::opus_multistream_encode_float(
+                self.encoder,
+                input.as_ptr(),
+                frame_size as c_int,
+                output.as_mut_ptr(),
+                max_size as c_int,
+            )
+        })?;
+        output.truncate(len as usize);
+        Ok(output)
+    }
+}
+
+impl Drop for MultistreamEncoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_multistream_encoder_destroy(self.encoder) }
+    }
+}
+
+/// Decodes the packets described by a `StreamParameters` into interleaved samples in its layout.
+pub struct MultistreamDecoder {
+    decoder: *mut ffi::OpusMSDecoder,
+    layout: ChannelLayout,
+}
+
+// See `MultistreamEncoder`
+unsafe impl Send for MultistreamDecoder {}
+
+impl MultistreamDecoder {
+    pub fn new(parameters: &StreamParameters) -> anyhow::Result<Self> {
+        let channels = parameters.layout.channels();
+        if parameters.mapping.len() != channels {
+            anyhow::bail!("Mapping has {} entries for {} channels", parameters.mapping.len(), channels);
+        }
+        let mut error = 0;
+        let decoder = unsafe {
+            ffi::opus_multistream_decoder_create(
+                parameters.sample_rate as c_int,
+                channels as c_int,
+                parameters.streams as c_int,
+                parameters.coupled_streams as c_int,
+                parameters.mapping.as_ptr(),
+                &mut error,
+            )
+        };
+        check("opus_multistream_decoder_create", error)?;
+        if decoder.is_null() {
+            anyhow::bail!("opus_multistream_decoder_create returned no decoder");
+        }
+        Ok(Self {
+            decoder,
+            layout: parameters.layout,
+        })
+    }
+
+    pub fn layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    /// Decodes one packet into `output`, which must hold `MAX_FRAME_SIZE` samples per channel to fit any packet.
+    /// Returns the number of samples per channel.
+    pub fn decode_float(&mut self, input: &[u8], output: &mut [f32]) -> anyhow::Result<usize> {
+        let frame_size = output.len() / self.layout.channels();
+        let samples = check("opus_multistream_decode_float", unsafe {
+            ffi::opus_multistream_decode_float(
+                self.decoder,
+                input.as_ptr(),
+                input.len() as c_int,
+                output.as_mut_ptr(),
+                frame_size as c_int,
+                0,
+            )
+        })?;
+        Ok(samples as usize)
+    }
+}
+
+impl Drop for MultistreamDecoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_multistream_decoder_destroy(self.decoder) }
+    }
+}
+
+/// Spreads interleaved stereo over `layout`. The kira mixer only produces stereo, so surround layouts get
+/// a passive upmix: the front pair as is, a phantom centre from what both sides share, and surrounds from
+/// what differs between them, in opposite phase. The LFE stays silent, there's no crossover to feed it.
+pub fn from_stereo(layout: ChannelLayout, input: &[f32]) -> Vec<f32> {
+    // Derived channels are 3 dB down so the upmix doesn't come out louder than the stereo it's made of
+    const DERIVED: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    match layout {
+        ChannelLayout::Stereo => input.to_vec(),
+        ChannelLayout::Mono => input.chunks(2).map(|frame| (frame[0] + frame[1]) * 0.5).collect(),
+        ChannelLayout::Surround51 | ChannelLayout::Surround71 => {
+            let channels = layout.channels();
+            let mut output = vec![0.0; input.len() / 2 * channels];
+            for (frame, samples) in input.chunks(2).zip(output.chunks_mut(channels)) {
+                let (left, right) = (frame[0], frame[1]);
+                let centre = (left + right) * 0.5 * DERIVED;
+                let surround = (left - right) * 0.5 * DERIVED;
+                samples[0] = left;
+                samples[1] = centre;
+                samples[2] = right;
+                // 5.1 has one surround pair, 7.1 a side and a rear pair, all of them before the LFE
+                for pair in samples[3..channels - 1].chunks_mut(2) {
+                    pair[0] = surround;
+                    pair[1] = -surround;
+                }
+            }
+            output
+        }
+    }
+}
+
+/// Mixes interleaved samples in `layout` down to `output_channels`, dropping the LFE.
+/// Devices with at least as many channels get the samples unchanged.
+pub fn downmix(layout: ChannelLayout, input: &[f32], output_channels: usize) -> Vec<f32> {
+    let channels = layout.channels();
+    if output_channels >= channels {
+        return input.to_vec();
+    }
+    // Left and right gains per input channel. 7.1 splits what a 5.1 surround would get between its side and rear
+    // pairs, so both layouts fold back the same way.
+    const SIDE: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    const SPLIT: f32 = SIDE * 0.5;
+    let gains: &[(f32, f32)] = match layout {
+        ChannelLayout::Mono => &[(1.0, 1.0)],
+        ChannelLayout::Stereo => &[(1.0, 0.0), (0.0, 1.0)],
+        ChannelLayout::Surround51 => &[(1.0, 0.0), (SIDE, SIDE), (0.0, 1.0), (SIDE, 0.0), (0.0, SIDE), (0.0, 0.0)],
+        ChannelLayout::Surround71 => &[
+            (1.0, 0.0), (SIDE, SIDE), (0.0, 1.0), (SPLIT, 0.0), (0.0, SPLIT), (SPLIT, 0.0), (0.0, SPLIT), (0.0, 0.0),
+        ],
+    };
+    let mix = |frame: &[f32]| {
+        frame.iter().zip(gains).fold((0.0, 0.0), |(left, right), (sample, (left_gain, right_gain))| {
+            (left + sample * left_gain, right + sample * right_gain)
+        })
+    };
+    // Scaled so stereo upmixed by from_stereo comes back at the level it went in
+    let norm = 1.0 / mix(&from_stereo(layout, &[1.0, 0.0])).0;
+    let mut output = Vec::with_capacity(input.len() / channels * output_channels);
+    for frame in input.chunks(channels) {
+        let (left, right) = mix(frame);
+        let (left, right) = (left * norm, right * norm);
+        if output_channels == 1 {
+            output.push((left + right) * 0.5);
+        } else {
+            output.push(left);
+            output.push(right);
+            output.extend(std::iter::repeat_n(0.0, output_channels - 2));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mono_averages_both_sides() {
+        assert_eq!(from_stereo(ChannelLayout::Mono, &[1.0, 0.0, 0.5, 0.5]), vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn surround_gets_a_phantom_centre() {
+        let half = 0.5 * std::f32::consts::FRAC_1_SQRT_2;
+        let output = from_stereo(ChannelLayout::Surround51, &[0.5, 0.5]);
+        assert_eq!(output.len(), 6);
+        assert_eq!(&output[..3], &[0.5, half, 0.5]);
+        // Nothing differs between the sides, so the surrounds and the LFE stay silent
+        assert_eq!(&output[3..], &[0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn surrounds_carry_the_difference() {
+        let half = 0.5 * std::f32::consts::FRAC_1_SQRT_2;
+        let output = from_stereo(ChannelLayout::Surround71, &[1.0, 0.0]);
+        assert_eq!(output, vec![1.0, half, 0.0, half, -half, half, -half, 0.0]);
+        let output = from_stereo(ChannelLayout::Surround51, &[1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(output, vec![1.0, half, 0.0, half, -half, 0.0, 0.0, half, 1.0, -half, half, 0.0]);
+    }
+
+    #[test]
+    fn surround_downmixes_back_to_the_stereo_it_came_from() {
+        let stereo = [1.0, 0.0, 0.0, 1.0, 0.5, 0.5, 0.8, -0.3];
+        for layout in [ChannelLayout::Surround51, ChannelLayout::Surround71] {
+            let output = downmix(layout, &from_stereo(layout, &stereo), 2);
+            assert_eq!(output.len(), stereo.len());
+            for (output, input) in output.iter().zip(stereo) {
+                assert!((output - input).abs() < 1e-6, "{:?}: {:?}", layout, output);
+            }
+        }
+    }
+}
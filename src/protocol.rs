@@ -0,0 +1,255 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+
+// Every frame on the wire is a one byte tag, a big-endian u32 payload length and the payload.
+const HEADER_SIZE: usize = 5;
+const MAX_PAYLOAD_SIZE: usize = 1 << 20;
+
+const AUDIO_TAG: u8 = 0;
+const END_SESSION_TAG: u8 = 1;
+const JOIN_TAG: u8 = 2;
+const STREAM_TAG: u8 = 3;
+const KEEPALIVE_TAG: u8 = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Audio(Bytes),
+    EndSession(String),
+    Join(Join),
+    Stream(StreamParameters),
+    /// Sent instead of audio while the host is silent.
+    Keepalive,
+}
+
+/// Sent by a client right after connecting. An empty channel joins the host's first channel.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Join {
+    pub username: String,
+    pub channel: String,
+    /// How the client wants the audio encoded. Clients from before lossless streams always get Opus.
+    #[serde(default)]
+    pub codec: Codec,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    Opus,
+    /// Interleaved 16-bit little-endian samples.
+    Pcm,
+    /// The same 16-bit samples, one FLAC frame per packet.
+    Flac,
+}
+
+impl Codec {
+    pub const ALL: [Codec; 3] = [Self::Opus, Self::Pcm, Self::Flac];
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Opus => "Opus",
+            Self::Pcm => "PCM (lossless)",
+            Self::Flac => "FLAC (lossless)",
+        })
+    }
+}
+
+/// Channel layouts in Vorbis channel order, as Opus mapping families 0 and 1 use them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelLayout {
+    Mono,
+    #[default]
+    Stereo,
+    /// Front left, center, front right, rear left, rear right, LFE.
+    Surround51,
+    /// Front left, center, front right, side left, side right, rear left, rear right, LFE.
+    Surround71,
+}
+
+impl ChannelLayout {
+    pub const ALL: [ChannelLayout; 4] = [Self::Mono, Self::Stereo, Self::Surround51, Self::Surround71];
+
+    pub const fn channels(&self) -> usize {
+        match self {
+            Self::Mono => 1,
+            Self::Stereo => 2,
+            Self::Surround51 => 6,
+            Self::Surround71 => 8,
+        }
+    }
+}
+
+impl std::fmt::Display for ChannelLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Mono => "Mono",
+            Self::Stereo => "Stereo",
+            Self::Surround51 => "5.1",
+            Self::Surround71 => "7.1",
+        })
+    }
+}
+
+/// Everything a client needs to set up its decoder. Sent after joining and whenever it changes,
+/// in order with the audio frames, so every packet after it is encoded this way.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamParameters {
+    #[serde(default)]
+    pub codec: Codec,
+    pub sample_rate: u32,
+    pub layout: ChannelLayout,
+    /// The Opus multistream setup, empty for the lossless codecs.
+    pub streams: u8,
+    pub coupled_streams: u8,
+    pub mapping: Vec<u8>,
+    /// Samples per channel in each packet.
+    pub frame_size: usize,
+}
+
+impl Frame {
+    pub fn encode(&self) -> Vec<u8> {
+        let json;
+        let (tag, payload): (u8, &[u8]) = match self {
+            Frame::Audio(packet) => (AUDIO_TAG, packet),
+            Frame::EndSession(reason) => (END_SESSION_TAG, reason.as_bytes()),
+            Frame::Join(join) => {
+                json = serde_json::to_vec(join).expect("Join serializes to JSON");
+                (JOIN_TAG, &json)
+            }
+            Frame::Stream(parameters) => {
+                json = serde_json::to_vec(parameters).expect("StreamParameters serializes to JSON");
+                (STREAM_TAG, &json)
+            }
+            Frame::Keepalive => (KEEPALIVE_TAG, &[]),
+        };
+        let mut buf = Vec::with_capacity(HEADER_SIZE + payload.len());
+        buf.put_u8(tag);
+        buf.put_u32(payload.len() as u32);
+        buf.put_slice(payload);
+        buf
+    }
+
+    /// Takes the next complete frame off the front of `buffer`, or returns `None` if more bytes are needed.
+    pub fn decode(buffer: &mut BytesMut) -> anyhow::Result<Option<Frame>> {
+        if buffer.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+        let tag = buffer[0];
+        let len = u32::from_be_bytes(buffer[1..HEADER_SIZE].try_into()?) as usize;
+        if len > MAX_PAYLOAD_SIZE {
+            anyhow::bail!("Frame payload too large: {} bytes", len);
+        }
+        if buffer.len() < HEADER_SIZE + len {
+            buffer.reserve(HEADER_SIZE + len - buffer.len());
+            return Ok(None);
+        }
+        buffer.advance(HEADER_SIZE);
+        let payload = buffer.split_to(len).freeze();
+
+        match tag {
+            AUDIO_TAG => Ok(Some(Frame::Audio(payload))),
+            END_SESSION_TAG => Ok(Some(Frame::EndSession(String::from_utf8_lossy(&payload).to_string()))),
+            JOIN_TAG => Ok(Some(Frame::Join(serde_json::from_slice(&payload)?))),
+            STREAM_TAG => Ok(Some(Frame::Stream(serde_json::from_slice(&payload)?))),
+            KEEPALIVE_TAG => Ok(Some(Frame::Keepalive)),
+            _ => anyhow::bail!("Unknown frame tag {}", tag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_frames() -> Vec<Frame> {
+        vec![
+            Frame::Audio(Bytes::from_static(&[1, 2, 3, 4])),
+            Frame::Audio(Bytes::new()),
+            Frame::EndSession(String::from("Host closed the room")),
+            Frame::Join(Join {
+                username: String::from("listener"),
+                channel: String::from("Main"),
+                codec: Codec::Flac,
+            }),
+            Frame::Stream(StreamParameters {
+                codec: Codec::Opus,
+                sample_rate: 48000,
+                layout: ChannelLayout::Surround51,
+                streams: 4,
+                coupled_streams: 2,
+                mapping: vec![0, 4, 1, 2, 3, 5],
+                frame_size: 960,
+            }),
+            Frame::Keepalive,
+        ]
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        for frame in all_frames() {
+            let mut buffer = BytesMut::from(frame.encode().as_slice());
+            assert_eq!(Frame::decode(&mut buffer).unwrap(), Some(frame));
+            assert!(buffer.is_empty());
+        }
+    }
+
+    #[test]
+    fn back_to_back_frames_decode_in_order() {
+        let frames = all_frames();
+        let mut buffer = BytesMut::new();
+        for frame in &frames {
+            buffer.extend_from_slice(&frame.encode());
+        }
+        for frame in frames {
+            assert_eq!(Frame::decode(&mut buffer).unwrap(), Some(frame));
+        }
+        assert_eq!(Frame::decode(&mut buffer).unwrap(), None);
+    }
+
+    #[test]
+    fn incomplete_frames_wait_for_more_bytes() {
+        let frame = Frame::EndSession(String::from("bye"));
+        let encoded = frame.encode();
+        let mut buffer = BytesMut::new();
+        for byte in &encoded[..encoded.len() - 1] {
+            buffer.extend_from_slice(&[*byte]);
+            assert_eq!(Frame::decode(&mut buffer).unwrap(), None);
+        }
+        // Nothing is consumed until the whole frame is there
+        assert_eq!(buffer.len(), encoded.len() - 1);
+        buffer.extend_from_slice(&encoded[encoded.len() - 1..]);
+        assert_eq!(Frame::decode(&mut buffer).unwrap(), Some(frame));
+    }
+
+    #[test]
+    fn oversized_payloads_are_rejected() {
+        let mut buffer = BytesMut::new();
+        buffer.put_u8(AUDIO_TAG);
+        buffer.put_u32(MAX_PAYLOAD_SIZE as u32 + 1);
+        assert!(Frame::decode(&mut buffer).is_err());
+
+        let mut buffer = BytesMut::new();
+        buffer.put_u8(AUDIO_TAG);
+        buffer.put_u32(MAX_PAYLOAD_SIZE as u32);
+        assert_eq!(Frame::decode(&mut buffer).unwrap(), None);
+    }
+
+    #[test]
+    fn unknown_tags_are_rejected() {
+        let mut buffer = BytesMut::new();
+        buffer.put_u8(255);
+        buffer.put_u32(2);
+        buffer.put_slice(&[0, 0]);
+        assert!(Frame::decode(&mut buffer).is_err());
+    }
+
+    #[test]
+    fn malformed_json_is_rejected() {
+        let mut buffer = BytesMut::new();
+        buffer.put_u8(JOIN_TAG);
+        buffer.put_u32(1);
+        buffer.put_u8(b'{');
+        assert!(Frame::decode(&mut buffer).is_err());
+    }
+}
@@ -1,6 +1,8 @@
+use crate::multistream::{Application, Bandwidth, FrameDuration};
+use crate::protocol::ChannelLayout;
 use serde::{Deserialize, Serialize};
-use crate::host::host::Host;
-use crate::settings;
+
+pub const DEFAULT_ROOM_NAME: &str = "Main";
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Mode {
@@ -8,11 +10,312 @@ pub enum Mode {
     Client,
 }
 
+/// Where the host's mixer plays to. Clients get the same audio either way.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioOutput {
+    #[default]
+    Device,
+    None,
+}
+
+/// Opus encoder settings the host can change while streaming.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct StreamQuality {
+    /// Kilobits per second for each channel of the layout.
+    pub bitrate: u32,
+    pub frame_duration: FrameDuration,
+    /// 0 to 10.
+    pub complexity: u8,
+    pub vbr: bool,
+    pub bandwidth: Bandwidth,
+    pub application: Application,
+}
+
+impl Default for StreamQuality {
+    fn default() -> Self {
+        Self {
+            bitrate: 32,
+            frame_duration: FrameDuration::default(),
+            complexity: 10,
+            vbr: true,
+            bandwidth: Bandwidth::default(),
+            application: Application::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TalkMode {
+    #[default]
+    PushToTalk,
+    VoiceActivation,
+}
+
+impl TalkMode {
+    pub const ALL: [TalkMode; 2] = [Self::PushToTalk, Self::VoiceActivation];
+}
+
+impl std::fmt::Display for TalkMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::PushToTalk => "Push to talk",
+            Self::VoiceActivation => "Voice activation",
+        })
+    }
+}
+
+/// What happens when a track ends.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackMode {
+    #[default]
+    LoopTrack,
+    PlayOnce,
+    /// Plays the next track, and stops after the last one.
+    Advance,
+    RepeatPlaylist,
+    /// Plays every track once in random order, then starts a new round.
+    Shuffle,
+}
+
+impl PlaybackMode {
+    pub const ALL: [PlaybackMode; 5] = [Self::LoopTrack, Self::PlayOnce, Self::Advance, Self::RepeatPlaylist, Self::Shuffle];
+}
+
+impl std::fmt::Display for PlaybackMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::LoopTrack => "Loop track",
+            Self::PlayOnce => "Play once",
+            Self::Advance => "Play next",
+            Self::RepeatPlaylist => "Repeat playlist",
+            Self::Shuffle => "Shuffle",
+        })
+    }
+}
+
+/// How the host's microphone is mixed over the music.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TalkOver {
+    pub mode: TalkMode,
+    /// Voice activation opens above this level, in dBFS.
+    pub threshold: f32,
+    /// How far the music drops while talking, in dB.
+    pub duck_depth: f32,
+    /// How long the music takes to come back after talking, in milliseconds.
+    pub release: u64,
+}
+
+impl Default for TalkOver {
+    fn default() -> Self {
+        Self {
+            mode: TalkMode::default(),
+            threshold: -40.0,
+            duck_depth: -15.0,
+            release: 800,
+        }
+    }
+}
+
+/// What a keyboard shortcut does on the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shortcut {
+    PlayPause,
+    Stop,
+    NextTrack,
+    PreviousTrack,
+    FadeOut,
+    VolumeUp,
+    VolumeDown,
+}
+
+impl Shortcut {
+    pub const ALL: [Shortcut; 7] = [Self::PlayPause, Self::Stop, Self::NextTrack, Self::PreviousTrack, Self::FadeOut, Self::VolumeUp, Self::VolumeDown];
+}
+
+impl std::fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::PlayPause => "Play / pause",
+            Self::Stop => "Stop",
+            Self::NextTrack => "Next track",
+            Self::PreviousTrack => "Previous track",
+            Self::FadeOut => "Fade out",
+            Self::VolumeUp => "Volume up",
+            Self::VolumeDown => "Volume down",
+        })
+    }
+}
+
+/// A key with the modifiers held down along with it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    /// A lowercase character, or the name of a named key such as `Space` or `ArrowUp`.
+    pub key: String,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub alt: bool,
+    #[serde(default)]
+    pub shift: bool,
+}
+
+impl KeyBinding {
+    fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+}
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            f.write_str("Ctrl+")?;
+        }
+        if self.alt {
+            f.write_str("Alt+")?;
+        }
+        if self.shift {
+            f.write_str("Shift+")?;
+        }
+        f.write_str(&self.key)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Shortcuts {
+    pub play_pause: Option<KeyBinding>,
+    pub stop: Option<KeyBinding>,
+    pub next_track: Option<KeyBinding>,
+    pub previous_track: Option<KeyBinding>,
+    pub fade_out: Option<KeyBinding>,
+    pub volume_up: Option<KeyBinding>,
+    pub volume_down: Option<KeyBinding>,
+    /// Whether the number keys 1 to 9 play the track with that number.
+    pub number_keys_play_tracks: bool,
+}
+
+impl Shortcuts {
+    pub fn get(&self, shortcut: Shortcut) -> Option<&KeyBinding> {
+        match shortcut {
+            Shortcut::PlayPause => self.play_pause.as_ref(),
+            Shortcut::Stop => self.stop.as_ref(),
+            Shortcut::NextTrack => self.next_track.as_ref(),
+            Shortcut::PreviousTrack => self.previous_track.as_ref(),
+            Shortcut::FadeOut => self.fade_out.as_ref(),
+            Shortcut::VolumeUp => self.volume_up.as_ref(),
+            Shortcut::VolumeDown => self.volume_down.as_ref(),
+        }
+    }
+
+    /// Binds `shortcut`, taking the key binding away from any other shortcut that had it.
+    pub fn set(&mut self, shortcut: Shortcut, binding: Option<KeyBinding>) {
+        if binding.is_some() {
+            for other in Shortcut::ALL {
+                if self.get(other) == binding.as_ref() {
+                    *self.get_mut(other) = None;
+                }
+            }
+        }
+        *self.get_mut(shortcut) = binding;
+    }
+
+    fn get_mut(&mut self, shortcut: Shortcut) -> &mut Option<KeyBinding> {
+        match shortcut {
+            Shortcut::PlayPause => &mut self.play_pause,
+            Shortcut::Stop => &mut self.stop,
+            Shortcut::NextTrack => &mut self.next_track,
+            Shortcut::PreviousTrack => &mut self.previous_track,
+            Shortcut::FadeOut => &mut self.fade_out,
+            Shortcut::VolumeUp => &mut self.volume_up,
+            Shortcut::VolumeDown => &mut self.volume_down,
+        }
+    }
+
+    pub fn find(&self, binding: &KeyBinding) -> Option<Shortcut> {
+        Shortcut::ALL.into_iter().find(|shortcut| self.get(*shortcut) == Some(binding))
+    }
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self {
+            play_pause: Some(KeyBinding::new("Space")),
+            stop: Some(KeyBinding::new("Escape")),
+            next_track: Some(KeyBinding::new("ArrowRight")),
+            previous_track: Some(KeyBinding::new("ArrowLeft")),
+            fade_out: Some(KeyBinding::new("f")),
+            volume_up: Some(KeyBinding::new("ArrowUp")),
+            volume_down: Some(KeyBinding::new("ArrowDown")),
+            number_keys_play_tracks: true,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Settings {
     pub fade_in_duration: u64,
     pub fade_out_duration: u64,
     pub mode: Mode,
+    #[serde(default = "default_rooms")]
+    pub rooms: Vec<String>,
+    #[serde(default)]
+    pub audio_output: AudioOutput,
+    #[serde(default)]
+    pub broadcast_only: bool,
+    #[serde(default)]
+    pub channel_layout: ChannelLayout,
+    #[serde(default)]
+    pub stream_quality: StreamQuality,
+    #[serde(default)]
+    pub talk_over: TalkOver,
+    #[serde(default)]
+    pub playback_mode: PlaybackMode,
+    /// How long consecutive tracks overlap when the playlist moves on by itself, in milliseconds.
+    #[serde(default = "default_crossfade_overlap")]
+    pub crossfade_overlap: u64,
+    #[serde(default)]
+    pub shortcuts: Shortcuts,
+    #[serde(default = "default_sfx_volume")]
+    pub sfx_volume: f64,
+    /// How many sound effects can play at once before the oldest is cut off.
+    #[serde(default = "default_sfx_polyphony")]
+    pub sfx_polyphony: usize,
+    /// How long recalling a scene crossfades into it, in milliseconds.
+    #[serde(default = "default_scene_transition")]
+    pub scene_transition: u64,
+    #[serde(default)]
+    pub normalize_loudness: bool,
+    /// The loudness tracks are normalized to, in LUFS.
+    #[serde(default = "default_target_loudness")]
+    pub target_loudness: f64,
+}
+
+fn default_rooms() -> Vec<String> {
+    vec![String::from(DEFAULT_ROOM_NAME)]
+}
+
+fn default_crossfade_overlap() -> u64 {
+    3000
+}
+
+fn default_sfx_volume() -> f64 {
+    1.0
+}
+
+fn default_sfx_polyphony() -> usize {
+    8
+}
+
+fn default_scene_transition() -> u64 {
+    4000
+}
+
+fn default_target_loudness() -> f64 {
+    -23.0
 }
 
 impl Default for Settings {
@@ -21,15 +324,61 @@ impl Default for Settings {
             fade_in_duration: 1000,
             fade_out_duration: 1000,
             mode: Mode::Host,
+            rooms: default_rooms(),
+            audio_output: AudioOutput::default(),
+            broadcast_only: false,
+            channel_layout: ChannelLayout::default(),
+            stream_quality: StreamQuality::default(),
+            talk_over: TalkOver::default(),
+            playback_mode: PlaybackMode::default(),
+            crossfade_overlap: default_crossfade_overlap(),
+            shortcuts: Shortcuts::default(),
+            sfx_volume: default_sfx_volume(),
+            sfx_polyphony: default_sfx_polyphony(),
+            scene_transition: default_scene_transition(),
+            normalize_loudness: false,
+            target_loudness: default_target_loudness(),
         }
     }
 }
 
-pub fn save(host: &Host) -> Result<(), confy::ConfyError> {
-    let settings = Settings {
-        fade_in_duration: host.fade_in_duration,
-        fade_out_duration: host.fade_out_duration,
-        mode: Mode::Host,
-    };
-    confy::store("multiplayer", None, &settings)
-}
\ No newline at end of file
+pub fn load() -> Settings {
+    confy::load("multiplayer", None).unwrap_or_default()
+}
+
+pub fn save(settings: &Settings) -> Result<(), confy::ConfyError> {
+    confy::store("multiplayer", None, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setting_a_taken_binding_steals_it() {
+        let mut shortcuts = Shortcuts::default();
+        shortcuts.set(Shortcut::FadeOut, Some(KeyBinding::new("Space")));
+        assert_eq!(shortcuts.get(Shortcut::FadeOut), Some(&KeyBinding::new("Space")));
+        assert_eq!(shortcuts.get(Shortcut::PlayPause), None);
+        assert_eq!(shortcuts.get(Shortcut::Stop), Some(&KeyBinding::new("Escape")));
+    }
+
+    #[test]
+    fn clearing_a_binding_leaves_the_others() {
+        let mut shortcuts = Shortcuts::default();
+        shortcuts.set(Shortcut::Stop, None);
+        assert_eq!(shortcuts.get(Shortcut::Stop), None);
+        assert_eq!(shortcuts.get(Shortcut::PlayPause), Some(&KeyBinding::new("Space")));
+    }
+
+    #[test]
+    fn find_matches_modifiers_too() {
+        let mut shortcuts = Shortcuts::default();
+        assert_eq!(shortcuts.find(&KeyBinding::new("ArrowUp")), Some(Shortcut::VolumeUp));
+        let ctrl_up = KeyBinding { ctrl: true, ..KeyBinding::new("ArrowUp") };
+        assert_eq!(shortcuts.find(&ctrl_up), None);
+        shortcuts.set(Shortcut::NextTrack, Some(ctrl_up.clone()));
+        assert_eq!(shortcuts.find(&ctrl_up), Some(Shortcut::NextTrack));
+        assert_eq!(shortcuts.find(&KeyBinding::new("ArrowUp")), Some(Shortcut::VolumeUp));
+    }
+}